@@ -0,0 +1,91 @@
+/**
+ * Reads asset files on a background thread so startup doesn't block on the disk.
+ * Only the bytes are read here, images are made from them on the main thread since that needs the ggez Context.
+ * A minimal set of assets is embedded in the binary, so the GUI runs without the resources directory.
+ * Files in the resources directory replace the embedded ones.
+ */
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// The embedded font, the same as the bundled one.
+pub const EMBEDDED_FONT: &[u8] = include_bytes!("../resources/fonts/DejaVuSans.ttf");
+
+/// The embedded copy of an asset, None if it isn't embedded.
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+    let bytes: &'static [u8] = match name.trim_start_matches('/') {
+        "black-king.png" => include_bytes!("../resources/pieces-png/black-king.png"),
+        "black-queen.png" => include_bytes!("../resources/pieces-png/black-queen.png"),
+        "black-rook.png" => include_bytes!("../resources/pieces-png/black-rook.png"),
+        "black-bishop.png" => include_bytes!("../resources/pieces-png/black-bishop.png"),
+        "black-knight.png" => include_bytes!("../resources/pieces-png/black-knight.png"),
+        "black-pawn.png" => include_bytes!("../resources/pieces-png/black-pawn.png"),
+        "white-king.png" => include_bytes!("../resources/pieces-png/white-king.png"),
+        "white-queen.png" => include_bytes!("../resources/pieces-png/white-queen.png"),
+        "white-rook.png" => include_bytes!("../resources/pieces-png/white-rook.png"),
+        "white-bishop.png" => include_bytes!("../resources/pieces-png/white-bishop.png"),
+        "white-knight.png" => include_bytes!("../resources/pieces-png/white-knight.png"),
+        "white-pawn.png" => include_bytes!("../resources/pieces-png/white-pawn.png"),
+        "DejaVuSans.ttf" => EMBEDDED_FONT,
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// An asset file that has been read, `bytes` is None when it is missing, can't be read and isn't embedded.
+pub struct Loaded {
+    pub name: String,
+
+    pub bytes: Option<Vec<u8>>,
+}
+
+#[derive(Clone)]
+pub struct AssetLoader {
+    // Files read but not taken by the GUI yet.
+    loaded: Arc<Mutex<Vec<Loaded>>>,
+
+    total: usize,
+
+    taken: usize,
+}
+
+impl AssetLoader {
+
+    /// Starts reading the files on their own thread, a name is looked up in each directory in order.
+    pub fn start(names: Vec<String>, dirs: Vec<PathBuf>) -> AssetLoader {
+        let loaded = Arc::new(Mutex::new(vec![]));
+        let total = names.len();
+
+        let shared = loaded.clone();
+        thread::spawn(move || {
+            for name in names {
+                let bytes = dirs.iter()
+                    .find_map(|dir| fs::read(dir.join(name.trim_start_matches('/'))).ok())
+                    .or_else(|| embedded(&name).map(|bytes| bytes.to_vec()));
+                shared.lock().expect("Asset lock poisoned").push(Loaded { name, bytes });
+            }
+        });
+
+        AssetLoader { loaded, total, taken: 0 }
+    }
+
+    /// The files read since the last call.
+    pub fn take(&mut self) -> Vec<Loaded> {
+        let loaded = std::mem::take(&mut *self.loaded.lock().expect("Asset lock poisoned"));
+        self.taken += loaded.len();
+        loaded
+    }
+
+    /// How much has been taken, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 { 1.0 } else { self.taken as f32 / self.total as f32 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.taken >= self.total
+    }
+}
@@ -0,0 +1,149 @@
+/**
+ * Names the opening of a game with its ECO code, from a small table of common openings embedded here.
+ * Openings are matched by position, so move orders that transpose into a line are found too.
+ */
+
+use chess::{Board, ChessMove};
+
+use crate::pgn;
+
+/// ECO code, name and main line in SAN of the known openings.
+const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A01", "Nimzowitsch-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A04", "Reti Opening", "Nf3"),
+    ("A10", "English Opening", "c4"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A45", "Indian Defence", "d4 Nf6"),
+    ("A56", "Benoni Defence", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A80", "Dutch Defence", "d4 f5"),
+    ("B00", "King's Pawn Opening", "e4"),
+    ("B01", "Scandinavian Defence", "e4 d5"),
+    ("B02", "Alekhine's Defence", "e4 Nf6"),
+    ("B06", "Modern Defence", "e4 g6"),
+    ("B07", "Pirc Defence", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defence", "e4 c6"),
+    ("B20", "Sicilian Defence", "e4 c5"),
+    ("B22", "Sicilian, Alapin", "e4 c5 c3"),
+    ("B23", "Sicilian, Closed", "e4 c5 Nc3"),
+    ("B30", "Sicilian Defence", "e4 c5 Nf3 Nc6"),
+    ("B40", "Sicilian Defence", "e4 c5 Nf3 e6"),
+    ("B50", "Sicilian Defence", "e4 c5 Nf3 d6"),
+    ("B54", "Sicilian, Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    ("B70", "Sicilian, Dragon", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6"),
+    ("B90", "Sicilian, Najdorf", "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6"),
+    ("C00", "French Defence", "e4 e6"),
+    ("C02", "French, Advance", "e4 e6 d4 d5 e5"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Centre Game", "e4 e5 d4 exd4"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defence", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defence", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Game", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C51", "Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C53", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+    ("C55", "Two Knights Defence", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez, Berlin", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C68", "Ruy Lopez, Exchange", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    ("C70", "Ruy Lopez, Morphy Defence", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4"),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "London System", "d4 d5 Bf4"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D10", "Slav Defence", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D80", "Grunfeld Defence", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E12", "Queen's Indian Defence", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defence", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defence", "d4 Nf6 c4 g6"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Opening {
+    pub eco: &'static str,
+
+    pub name: &'static str,
+}
+
+impl Opening {
+
+    /// Code and name, e.g. "C50 Italian Game".
+    pub fn label(&self) -> String {
+        format!("{} {}", self.eco, self.name)
+    }
+}
+
+/// The position at the end of every main line with its opening.
+fn positions() -> Vec<(Board, Opening)> {
+    OPENINGS.iter()
+        .filter_map(|(eco, name, line)| {
+            let mut board = Board::default();
+            for san in line.split_whitespace() {
+                board = board.make_move_new(pgn::parse_san(&board, san)?);
+            }
+            Some((board, Opening { eco: *eco, name: *name }))
+        })
+        .collect()
+}
+
+/// The opening whose main line ends in this position, None if it isn't in the table.
+pub fn lookup(board: &Board) -> Option<Opening> {
+    positions().into_iter().find(|(position, _)| position == board).map(|(_, opening)| opening)
+}
+
+/// The deepest known opening a game went through, only games from the starting position are classified.
+pub fn classify(start: &Board, moves: &[ChessMove]) -> Option<Opening> {
+    if *start != Board::default() {
+        return None;
+    }
+    let positions = positions();
+    let mut board = *start;
+    let mut opening = None;
+    for mv in moves.iter() {
+        board = board.make_move_new(*mv);
+        if let Some((_, found)) = positions.iter().find(|(position, _)| *position == board) {
+            opening = Some(*found);
+        }
+    }
+    opening
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opening_line_parses() {
+        for (eco, name, line) in OPENINGS.iter() {
+            let mut board = Board::default();
+            for san in line.split_whitespace() {
+                let mv = pgn::parse_san(&board, san).unwrap_or_else(|| panic!("{} {}: {} doesn't parse", eco, name, san));
+                board = board.make_move_new(mv);
+            }
+        }
+        assert_eq!(positions().len(), OPENINGS.len());
+    }
+
+    #[test]
+    fn classifies_the_deepest_known_opening() {
+        let mut board = Board::default();
+        let mut moves = vec![];
+        for san in "e4 e5 Nf3 Nc6 Bc4 Bc5 d3".split_whitespace() {
+            let mv = pgn::parse_san(&board, san).expect("Test move");
+            moves.push(mv);
+            board = board.make_move_new(mv);
+        }
+        assert_eq!(classify(&Board::default(), &moves).map(|opening| opening.eco), Some("C50"));
+        assert_eq!(classify(&board, &[]), None);
+    }
+}
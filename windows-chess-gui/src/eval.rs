@@ -0,0 +1,122 @@
+/**
+ * Lightweight position evaluation without an engine.
+ * Counts material and adds simple piece-square bonuses.
+ */
+
+use chess::{Board, Color, Piece};
+
+/// Piece values in centipawns, the king is never captured so it has no value.
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight => 320,
+        Piece::Bishop => 330,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
+
+// Piece-square tables seen from White, with the 8th rank on the first row.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// Bonus for a piece on a square, looked up from the owner's side of the board.
+fn square_bonus(piece: Piece, color: Color, sq: chess::Square) -> i32 {
+    let rank = sq.get_rank().to_index();
+    let file = sq.get_file().to_index();
+    let index = match color {
+        Color::White => (7 - rank) * 8 + file,
+        Color::Black => rank * 8 + file,
+    };
+
+    match piece {
+        Piece::Pawn => PAWN_TABLE[index],
+        Piece::Knight => KNIGHT_TABLE[index],
+        Piece::Bishop => BISHOP_TABLE[index],
+        Piece::King => KING_TABLE[index],
+        _ => 0,
+    }
+}
+
+/// Evaluates a position in centipawns, positive numbers are good for White.
+pub fn evaluate(board: &Board) -> i32 {
+    let mut score = 0;
+
+    for sq in *board.combined() {
+        if let (Some(piece), Some(color)) = (board.piece_on(sq), board.color_on(sq)) {
+            let value = piece_value(piece) + square_bonus(piece, color, sq);
+            match color {
+                Color::White => score += value,
+                Color::Black => score -= value,
+            }
+        }
+    }
+
+    score
+}
+
+/// Pieces of one color that are no longer on the board, pawns first.
+pub fn captured_pieces(board: &Board, color: Color) -> Vec<Piece> {
+    let start = [
+        (Piece::Pawn, 8),
+        (Piece::Knight, 2),
+        (Piece::Bishop, 2),
+        (Piece::Rook, 2),
+        (Piece::Queen, 1),
+    ];
+
+    let mut captured = vec![];
+    for (piece, count) in start.iter() {
+        let left = (*board.pieces(*piece) & *board.color_combined(color)).popcnt();
+        for _ in left..*count {
+            captured.push(*piece);
+        }
+    }
+    captured
+}
@@ -0,0 +1,100 @@
+/**
+ * Registry of the clickable regions of the window. Drawing asks it for the rectangles and hover states,
+ * and the mouse handler for the region under a click, so the two always agree.
+ * Regions are in unscaled screen coordinates, and a region added later lies on top of the ones before it.
+ */
+
+use ggez::graphics::Rect;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitRegion {
+    Board,
+
+    StartButton,
+
+    ReplaysButton,
+    // A game in the list that opens under the replays button, by its index in the list.
+    ReplayEntry(usize),
+
+    AnalysisButton,
+
+    RematchButton,
+
+    TopNamePlate,
+
+    BottomNamePlate,
+
+    ScaleSlider,
+    // A piece of the promotion picker, by its index in the picker.
+    PromotionPiece(usize),
+}
+
+#[derive(Clone, Default)]
+pub struct HitRegions {
+    regions: Vec<(HitRegion, Rect)>,
+}
+
+impl HitRegions {
+
+    pub fn add(&mut self, region: HitRegion, rect: Rect) {
+        self.regions.push((region, rect));
+    }
+
+    /// The topmost region at a point, None if there is nothing clickable there.
+    pub fn at(&self, x: f32, y: f32) -> Option<HitRegion> {
+        self.regions.iter().rev().find(|(_, rect)| rect.contains([x, y])).map(|(region, _)| *region)
+    }
+
+    /// The rectangle of a region, None if it isn't shown right now.
+    pub fn rect(&self, region: HitRegion) -> Option<Rect> {
+        self.regions.iter().find(|(other, _)| *other == region).map(|(_, rect)| *rect)
+    }
+
+    /// True when a point is over a region and nothing lies on top of it there.
+    pub fn hovered(&self, region: HitRegion, x: f32, y: f32) -> bool {
+        self.at(x, y) == Some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regions() -> HitRegions {
+        let mut regions = HitRegions::default();
+        regions.add(HitRegion::Board, Rect::new(20.0, 20.0, 720.0, 720.0));
+        regions.add(HitRegion::PromotionPiece(0), Rect::new(20.0, 20.0, 90.0, 90.0));
+        regions
+    }
+
+    #[test]
+    fn topmost_region_wins() {
+        let regions = regions();
+        assert_eq!(regions.at(50.0, 50.0), Some(HitRegion::PromotionPiece(0)));
+        assert_eq!(regions.at(300.0, 300.0), Some(HitRegion::Board));
+        assert_eq!(regions.at(5.0, 5.0), None);
+    }
+
+    #[test]
+    fn covered_region_is_not_hovered() {
+        let regions = regions();
+        assert!(!regions.hovered(HitRegion::Board, 50.0, 50.0));
+        assert!(regions.hovered(HitRegion::PromotionPiece(0), 50.0, 50.0));
+        assert!(regions.hovered(HitRegion::Board, 300.0, 300.0));
+    }
+
+    #[test]
+    fn missing_region_has_no_rect() {
+        let regions = regions();
+        assert_eq!(regions.rect(HitRegion::StartButton), None);
+        assert_eq!(regions.rect(HitRegion::Board), Some(Rect::new(20.0, 20.0, 720.0, 720.0)));
+    }
+
+    #[test]
+    fn edges_belong_to_the_region() {
+        let regions = regions();
+        assert_eq!(regions.at(20.0, 20.0), Some(HitRegion::PromotionPiece(0)));
+        assert_eq!(regions.at(740.0, 740.0), Some(HitRegion::Board));
+        assert_eq!(regions.at(740.1, 400.0), None);
+    }
+}
@@ -0,0 +1,130 @@
+/**
+ * Imports every game of a PGN file as replays, on its own thread since a database can have thousands of games.
+ * Games already imported, with the same players, date, start position and moves, are skipped.
+ */
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::{pgn, replay::{GameInfo, Replay}};
+
+/// At most this many error messages are kept for the report, the rest are only counted.
+const MAX_ERRORS: usize = 20;
+
+/// How far a game import has come, shared with the thread that runs it.
+#[derive(Clone, Default)]
+pub struct GameImport {
+    pub bytes_read: u64,
+
+    pub total_bytes: u64,
+    // Games read and added, waiting for the GUI to take them.
+    pub games: Vec<Replay>,
+    // Keys of the added games, so later imports skip them as duplicates.
+    pub keys: Vec<String>,
+
+    pub imported: usize,
+
+    pub duplicates: usize,
+    // Games that couldn't be read.
+    pub failed: usize,
+    // The first errors, e.g. "Game 12: Nf6 is not a legal move after 3 moves".
+    pub errors: Vec<String>,
+
+    pub done: bool,
+
+    pub error: Option<String>,
+}
+
+pub type SharedImport = Arc<Mutex<GameImport>>;
+
+/// What makes two games the same: players, date, start position and moves.
+fn key(text: &str, start: &chess::Board, moves: &[chess::ChessMove]) -> String {
+    let tag = |name: &str| pgn::tag(text, name).unwrap_or_default();
+    let moves = moves.iter().map(|mv| mv.to_string()).collect::<Vec<String>>().join(" ");
+    format!("{}|{}|{}|{}|{}", tag("White"), tag("Black"), tag("Date"), start, moves)
+}
+
+/// Reads one game and adds it to the import, unless it is a duplicate.
+fn add_game(text: &str, number: usize, known: &mut HashSet<String>, progress: &SharedImport) {
+    let read = pgn::read(text);
+    let mut progress = progress.lock().expect("Game import lock poisoned");
+    match read {
+        Ok((start, moves)) => {
+            let key = key(text, &start, &moves);
+            if known.insert(key.clone()) {
+                progress.games.push(Replay::new(start, &moves).with_info(GameInfo::from_pgn(text)));
+                progress.keys.push(key);
+                progress.imported += 1;
+            } else {
+                progress.duplicates += 1;
+            }
+        }
+        Err(e) => {
+            progress.failed += 1;
+            if progress.errors.len() < MAX_ERRORS {
+                progress.errors.push(format!("Game {}: {}", number, e));
+            }
+        }
+    }
+}
+
+/// Imports all games of a PGN file on its own thread, `known` are the keys of the games imported before.
+/// A new game starts at a tag line that comes after move text.
+pub fn import_pgn(path: PathBuf, mut known: HashSet<String>, progress: SharedImport) {
+    thread::spawn(move || {
+        let result = (|| -> io::Result<()> {
+            let file = fs::File::open(&path)?;
+            progress.lock().expect("Game import lock poisoned").total_bytes = file.metadata()?.len();
+
+            let mut game = String::new();
+            let mut has_moves = false;
+            let mut number = 0;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let trimmed = line.trim();
+                if trimmed.starts_with('[') && has_moves {
+                    number += 1;
+                    add_game(&game, number, &mut known, &progress);
+                    game.clear();
+                    has_moves = false;
+                }
+                if !trimmed.is_empty() && !trimmed.starts_with('[') {
+                    has_moves = true;
+                }
+                game.push_str(&line);
+                game.push('\n');
+                progress.lock().expect("Game import lock poisoned").bytes_read += line.len() as u64 + 1;
+            }
+            if has_moves {
+                add_game(&game, number + 1, &mut known, &progress);
+            }
+            Ok(())
+        })();
+
+        let mut progress = progress.lock().expect("Game import lock poisoned");
+        progress.done = true;
+        if let Err(e) = result {
+            progress.error = Some(e.to_string());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_tells_games_apart_by_tags() {
+        let game = "[White \"A\"]\n[Black \"B\"]\n[Date \"2022.10.16\"]\n\n1. e4 e5 *\n";
+        let (start, moves) = pgn::read(game).expect("Test game");
+        assert_eq!(key(game, &start, &moves), key(&game.replace("\n\n", "\n[Event \"Club\"]\n\n"), &start, &moves));
+        assert_ne!(key(game, &start, &moves), key(&game.replace("2022.10.16", "2022.10.17"), &start, &moves));
+        assert_ne!(key(game, &start, &moves), key(game, &start, &moves[..1]));
+    }
+}
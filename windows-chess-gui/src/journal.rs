@@ -0,0 +1,67 @@
+/**
+ * Append-only journal of the running game in the user config directory, written move by move
+ * so a crash or power loss keeps every move played.
+ * Line format: "start <fen>" begins a game, then a "move <uci>" line for every move and finally "result <text>".
+ * An unfinished game in the journal is played again when the GUI starts.
+ */
+
+use chess::{Board, ChessMove};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::remote;
+
+/// Adds a line to the journal and waits until it is on the disk.
+fn append(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    file.sync_data()
+}
+
+/// Starts a new journal for a game from a position, the journal of the last game is replaced.
+pub fn start(path: &Path, board: &Board) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, "")?;
+    append(path, &format!("start {}", board))
+}
+
+pub fn add_move(path: &Path, mv: ChessMove) -> io::Result<()> {
+    append(path, &format!("move {}", mv))
+}
+
+pub fn finish(path: &Path, result: &str) -> io::Result<()> {
+    append(path, &format!("result {}", result))
+}
+
+/// The start position and moves of a game the journal has no result for, None when there is nothing to recover.
+/// A line cut off by a crash ends the game there.
+pub fn unfinished(path: &Path) -> Option<(Board, Vec<ChessMove>)> {
+    let text = fs::read_to_string(path).ok()?;
+    let mut start = None;
+    let mut moves = vec![];
+    for line in text.lines() {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match word {
+            "start" => {
+                start = Some(Board::from_str(rest).ok()?);
+                moves.clear();
+            }
+            "move" => match remote::parse_uci(rest) {
+                Some(mv) => moves.push(mv),
+                None => break,
+            },
+            "result" => return None,
+            _ => break,
+        }
+    }
+    if moves.is_empty() {
+        return None;
+    }
+    start.map(|start| (start, moves))
+}
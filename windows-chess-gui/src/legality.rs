@@ -0,0 +1,154 @@
+/**
+ * Explains why a move is not legal, for beginners who try one.
+ * The reason is found by checking what the move breaks: how the piece moves, pieces in the way,
+ * a pin, a check or one of the castling rules.
+ */
+
+use chess::{BitBoard, Board, ChessMove, File, Piece, Square, EMPTY};
+
+fn piece_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "pawn",
+        Piece::Knight => "knight",
+        Piece::Bishop => "bishop",
+        Piece::Rook => "rook",
+        Piece::Queen => "queen",
+        Piece::King => "king",
+    }
+}
+
+/// The squares a piece can go to by how it moves, with or without the other pieces in the way.
+fn targets(board: &Board, piece: Piece, from: Square, blockers: BitBoard) -> BitBoard {
+    let color = board.side_to_move();
+    match piece {
+        Piece::Pawn => {
+            //en passant goes to an empty square behind the pawn it captures
+            let en_passant = board.en_passant().and_then(|sq| sq.forward(color)).map_or(EMPTY, BitBoard::from_square);
+            chess::get_pawn_moves(from, color, blockers | en_passant)
+        }
+        Piece::Knight => chess::get_knight_moves(from),
+        Piece::Bishop => chess::get_bishop_moves(from, blockers),
+        Piece::Rook => chess::get_rook_moves(from, blockers),
+        Piece::Queen => chess::get_bishop_moves(from, blockers) | chess::get_rook_moves(from, blockers),
+        Piece::King => chess::get_king_moves(from),
+    }
+}
+
+/// Why a pawn can't go to a square it can't reach.
+fn pawn_reason(board: &Board, from: Square, to: Square) -> &'static str {
+    let color = board.side_to_move();
+    let forward = from.forward(color);
+    if from.get_file() != to.get_file() {
+        let file_distance = (from.get_file().to_index() as i32 - to.get_file().to_index() as i32).abs();
+        if file_distance == 1 && forward.map(|sq| sq.get_rank()) == Some(to.get_rank()) && board.piece_on(to).is_none() {
+            return "A pawn only moves diagonally to capture";
+        }
+        return "A pawn can't move like that";
+    }
+    match forward {
+        Some(sq) if sq == to => "A pawn can't capture straight ahead",
+        Some(sq) if sq.forward(color) == Some(to) => {
+            if from.get_rank() != color.to_second_rank() {
+                "A pawn only moves two squares from its starting square"
+            } else {
+                "There is a piece in the way"
+            }
+        }
+        _ => "A pawn can't move like that",
+    }
+}
+
+/// Why a castling move isn't legal, the king moves two squares towards the rook.
+fn castling_reason(board: &Board, from: Square, to: Square) -> &'static str {
+    let rights = board.castle_rights(board.side_to_move());
+    let kingside = to.get_file().to_index() > from.get_file().to_index();
+    if !(if kingside { rights.has_kingside() } else { rights.has_queenside() }) {
+        return "You can't castle, the king or that rook has moved";
+    }
+    if *board.checkers() != EMPTY {
+        return "You can't castle out of check";
+    }
+    let rook = Square::make_square(from.get_rank(), if kingside { File::H } else { File::A });
+    if chess::between(from, rook) & *board.combined() != EMPTY {
+        return "You can't castle with pieces between the king and the rook";
+    }
+    //the king passes the square between from and to, a one square king move there shows if it is attacked
+    let passed = Square::make_square(from.get_rank(), File::from_index((from.get_file().to_index() + to.get_file().to_index()) / 2));
+    if !board.legal(ChessMove::new(from, passed, None)) {
+        return "You can't castle through check";
+    }
+    "You can't castle into check"
+}
+
+/// Why a move of the side to move is not legal, e.g. "That piece is pinned to your king".
+/// None if the move is legal or there is no piece of the side to move to make it.
+pub fn explain(board: &Board, mv: ChessMove) -> Option<String> {
+    if board.legal(mv) {
+        return None;
+    }
+    let (from, to) = (mv.get_source(), mv.get_dest());
+    let color = board.side_to_move();
+    if board.color_on(from) != Some(color) || from == to {
+        return None;
+    }
+    let piece = board.piece_on(from)?;
+
+    if board.color_on(to) == Some(color) {
+        return Some("You can't capture your own piece".to_string());
+    }
+    let file_distance = (from.get_file().to_index() as i32 - to.get_file().to_index() as i32).abs();
+    if piece == Piece::King && from.get_file() == File::E && from.get_rank() == to.get_rank() && file_distance == 2 {
+        return Some(castling_reason(board, from, to).to_string());
+    }
+
+    let blockers = *board.combined();
+    if targets(board, piece, from, blockers) & BitBoard::from_square(to) == EMPTY {
+        let reason = if piece == Piece::Pawn {
+            pawn_reason(board, from, to).to_string()
+        } else if targets(board, piece, from, EMPTY) & BitBoard::from_square(to) != EMPTY {
+            "There is a piece in the way".to_string()
+        } else {
+            format!("A {} can't move like that", piece_name(piece))
+        };
+        return Some(reason);
+    }
+
+    //the piece can go there, so the move leaves the king in check
+    let reason = if piece != Piece::King && *board.pinned() & BitBoard::from_square(from) != EMPTY {
+        "That piece is pinned to your king"
+    } else if piece == Piece::King {
+        "Your king would be in check there"
+    } else if *board.checkers() != EMPTY {
+        "Your king is in check, the move has to stop it"
+    } else {
+        "Your king would be in check"
+    };
+    Some(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn reason(fen: &str, from: Square, to: Square) -> Option<String> {
+        explain(&Board::from_str(fen).expect("Test FEN"), ChessMove::new(from, to, None))
+    }
+
+    #[test]
+    fn legal_moves_have_no_reason() {
+        assert_eq!(reason("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", Square::E2, Square::E4), None);
+    }
+
+    #[test]
+    fn explains_illegal_moves() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(reason(start, Square::B1, Square::B3).as_deref(), Some("A knight can't move like that"));
+        assert_eq!(reason(start, Square::A1, Square::A3).as_deref(), Some("There is a piece in the way"));
+        assert_eq!(reason(start, Square::E2, Square::D3).as_deref(), Some("A pawn only moves diagonally to capture"));
+        assert_eq!(reason(start, Square::D1, Square::D2).as_deref(), Some("You can't capture your own piece"));
+        assert_eq!(reason("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1", Square::E2, Square::D3).as_deref(), Some("That piece is pinned to your king"));
+        assert_eq!(reason("4kr2/8/8/8/8/8/8/4K2R w K - 0 1", Square::E1, Square::G1).as_deref(), Some("You can't castle through check"));
+        assert_eq!(reason("4k3/8/8/8/8/8/8/4K2R w - - 0 1", Square::E1, Square::G1).as_deref(), Some("You can't castle, the king or that rook has moved"));
+    }
+}
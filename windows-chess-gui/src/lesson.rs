@@ -0,0 +1,77 @@
+/**
+ * Lessons recorded in presentation mode, shown to students step by step.
+ * Every step is a position with the teacher's marks, arrows and a note, stored as text in a .lesson file.
+ * Line format: "step <fen>" begins a step, followed by "mark <square>", "arrow <square> <square>" and "note <text>" lines.
+ * A note can take several "note" lines, they are joined with line breaks.
+ */
+
+use chess::{Board, Square};
+use std::str::FromStr;
+
+/// One position of a lesson with what the teacher showed on it.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub board: Board,
+    // Highlighted squares.
+    pub marks: Vec<Square>,
+    // From and to square of every arrow.
+    pub arrows: Vec<(Square, Square)>,
+    // What the teacher said about the position, can be empty.
+    pub note: String,
+}
+
+/// Writes the steps of a lesson as the text of a .lesson file.
+pub fn write(steps: &[Step]) -> String {
+    let mut text = String::new();
+    for step in steps.iter() {
+        text.push_str(&format!("step {}\n", step.board));
+        for mark in step.marks.iter() {
+            text.push_str(&format!("mark {}\n", mark));
+        }
+        for (from, to) in step.arrows.iter() {
+            text.push_str(&format!("arrow {} {}\n", from, to));
+        }
+        for line in step.note.lines() {
+            text.push_str(&format!("note {}\n", line));
+        }
+    }
+    text
+}
+
+/// Reads the steps of a .lesson file, the error tells which line is wrong.
+pub fn read(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps: Vec<Step> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let square = |text: &str| Square::from_str(text.trim()).map_err(|_| format!("Line {}: {} is not a square", i + 1, text.trim()));
+
+        if word == "step" {
+            let board = Board::from_str(rest.trim()).map_err(|_| format!("Line {}: {} is not a FEN", i + 1, rest.trim()))?;
+            steps.push(Step { board, marks: vec![], arrows: vec![], note: String::new() });
+            continue;
+        }
+        let step = steps.last_mut().ok_or(format!("Line {}: the lesson has to begin with a step", i + 1))?;
+        match word {
+            "mark" => step.marks.push(square(rest)?),
+            "arrow" => {
+                let (from, to) = rest.trim().split_once(' ').ok_or(format!("Line {}: an arrow needs two squares", i + 1))?;
+                step.arrows.push((square(from)?, square(to)?));
+            }
+            "note" => {
+                if !step.note.is_empty() {
+                    step.note.push('\n');
+                }
+                step.note.push_str(rest);
+            }
+            _ => return Err(format!("Line {}: unknown line \"{}\"", i + 1, word)),
+        }
+    }
+    if steps.is_empty() {
+        return Err("The lesson has no steps".to_string());
+    }
+    Ok(steps)
+}
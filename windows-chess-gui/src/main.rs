@@ -4,108 +4,3355 @@
  * Last updated: 2022-10-16
  */
 
-use chess::{Game, Color, Piece, Board, BoardStatus, BitBoard, ChessMove};
+mod assets;
+mod eco;
+mod eval;
+mod hit;
+mod import;
+mod journal;
+mod lesson;
+mod legality;
+mod pgn;
+mod profiles;
+mod puzzles;
+mod notify;
+mod remote;
+mod replay;
+mod scoresheet;
+mod settings;
+mod structure;
+mod training;
+mod validate;
+mod vision;
+
+use chess::{Game, Color, Piece, Board, BoardStatus, BitBoard, ChessMove, MoveGen};
 use jblomlof_chess::{Game as ChessGame, GameState};
 
-use ggez::{conf, event::{self, winit_event}, graphics, Context, ContextBuilder, GameError, GameResult, input};
-use std::{collections::HashMap, path, str::FromStr, vec, time::{self, Duration, Instant}, thread};
+use ggez::{conf, event::{self, winit_event}, graphics, timer, Context, ContextBuilder, GameError, GameResult, input};
+use hit::HitRegion;
+use std::{collections::{HashMap, HashSet}, path, str::FromStr, vec, time::{self, Duration, Instant}, thread};
+
+/// A chess board is 8x8 tiles.
+const GRID_SIZE: i16 = 8;
+/// Sutible size of each tile.
+const GRID_CELL_SIZE: (i16, i16) = (90, 90);
+
+/// Size of the application window.
+const SCREEN_SIZE: (f32, f32) = (
+    GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 400.0,
+    GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32 + 40.0,
+);
+
+// GUI Color representations
+const BLACK: graphics::Color =
+    graphics::Color::new(93.0 / 255.0, 50.0 / 255.0, 49.0 / 255.0, 1.0);
+const WHITE: graphics::Color =
+    graphics::Color::new(121.0 / 255.0, 71.0 / 255.0, 56.0 / 255.0, 1.0);
+const _CIRCLE_GRAY: graphics::Color =
+    graphics::Color::new(128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 0.8);
+const BACKGROUND_COLOR: graphics::Color =
+    graphics::Color::new(49.0 / 255.0, 46.0 / 255.0, 43.0 / 255.0, 1.0);
+const MENU_COLOR: graphics::Color =
+    graphics::Color::new(39.0 / 255.0, 37.0 / 255.0, 34.0 / 255.0, 1.0);
+const BADGE_COLOR: graphics::Color =
+    graphics::Color::new(20.0 / 255.0, 20.0 / 255.0, 20.0 / 255.0, 0.85);
+// Chroma key green behind the board in stream mode.
+const STREAM_KEY_COLOR: graphics::Color =
+    graphics::Color::new(0.0, 177.0 / 255.0, 64.0 / 255.0, 1.0);
+
+/// Directory of the piece images in the resources, they are read by the asset loader rather than through ggez.
+/// The pieces and the icon are embedded too, these files replace them when they exist.
+const PIECES_DIR: &str = "pieces-png";
+
+/// The image file of every piece.
+const SPRITE_FILES: [((Color, Piece), &str); 12] = [
+    ((Color::Black, Piece::King), "/black-king.png"),
+    ((Color::Black, Piece::Queen), "/black-queen.png"),
+    ((Color::Black, Piece::Rook), "/black-rook.png"),
+    ((Color::Black, Piece::Pawn), "/black-pawn.png"),
+    ((Color::Black, Piece::Bishop), "/black-bishop.png"),
+    ((Color::Black, Piece::Knight), "/black-knight.png"),
+    ((Color::White, Piece::King), "/white-king.png"),
+    ((Color::White, Piece::Queen), "/white-queen.png"),
+    ((Color::White, Piece::Rook), "/white-rook.png"),
+    ((Color::White, Piece::Pawn), "/white-pawn.png"),
+    ((Color::White, Piece::Bishop), "/white-bishop.png"),
+    ((Color::White, Piece::Knight), "/white-knight.png"),
+];
+
+/// Square textures that can be put under the tile colors, (name, light square, dark square).
+const BOARD_TEXTURES: [(&str, &str, &str); 2] = [
+    ("Wood", "/square brown light_png_128px.png", "/square brown dark_png_128px.png"),
+    ("Stone", "/square gray light _png_128px.png", "/square gray dark _png_128px.png"),
+];
+
+
+/// Returns the square shown at a row and column of the board, counted from the top left corner.
+/// When `flipped` is true Black is at the bottom.
+fn screen_square(row: usize, col: usize, flipped: bool) -> chess::Square {
+    let (rank, file) = if flipped { (row, 7 - col) } else { (7 - row, col) };
+    chess::Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file))
+}
+
+/// Returns the square under a screen position, or None outside the board.
+fn square_at(x: f32, y: f32, flipped: bool) -> Option<chess::Square> {
+    let col = ((x - 20.0) / GRID_CELL_SIZE.0 as f32).floor();
+    let row = ((y - 20.0) / GRID_CELL_SIZE.1 as f32).floor();
+    if col < 0.0 || row < 0.0 || col >= GRID_SIZE as f32 || row >= GRID_SIZE as f32 {
+        return None;
+    }
+    Some(screen_square(row as usize, col as usize, flipped))
+}
+
+/// Returns the screen rectangle of a square on the board.
+fn square_rect(sq: chess::Square, flipped: bool) -> graphics::Rect {
+    let (row, col) = if flipped {
+        (sq.get_rank().to_index(), 7 - sq.get_file().to_index())
+    } else {
+        (7 - sq.get_rank().to_index(), sq.get_file().to_index())
+    };
+    graphics::Rect::new(
+        col as f32 * GRID_CELL_SIZE.0 as f32 + 20.0,
+        row as f32 * GRID_CELL_SIZE.1 as f32 + 20.0,
+        GRID_CELL_SIZE.0 as f32,
+        GRID_CELL_SIZE.1 as f32,
+    )
+}
+
+/// Returns the screen position of the center of a square on the board.
+fn square_center(sq: chess::Square, flipped: bool) -> [f32; 2] {
+    let rect = square_rect(sq, flipped);
+    [rect.x + rect.w / 2.0, rect.y + rect.h / 2.0]
+}
+
+/// Fill color of a menu button, a little darker under the mouse.
+fn button_color(hovered: bool) -> graphics::Color {
+    if hovered { graphics::Color::new(0.85, 0.85, 0.85, 1.0) } else { graphics::Color::new(1.0, 1.0, 1.0, 1.0) }
+}
+
+/// The pieces a pawn can promote to, in the order the promotion picker shows them.
+const PROMOTION_PIECES: [Piece; 4] = [Piece::Queen, Piece::Knight, Piece::Rook, Piece::Bishop];
+
+/// The squares of the promotion picker, a column from the promotion square towards the middle of the board.
+fn promotion_rects(to: chess::Square, flipped: bool) -> Vec<(Piece, graphics::Rect)> {
+    let first = square_rect(to, flipped);
+    let step = if first.y < 20.0 + 4.0 * GRID_CELL_SIZE.1 as f32 { first.h } else { -first.h };
+    PROMOTION_PIECES.iter()
+        .enumerate()
+        .map(|(i, piece)| (*piece, graphics::Rect::new(first.x, first.y + step * i as f32, first.w, first.h)))
+        .collect()
+}
+
+/// Squashes everything drawn after it vertically around the middle of the board, used when flipping.
+fn squash_transform(scale: f32) -> ggez::mint::ColumnMatrix4<f32> {
+    let center = 20.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32 / 2.0;
+    ggez::mint::ColumnMatrix4::from([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, scale, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, center * (1.0 - scale), 0.0, 1.0],
+    ])
+}
+
+/// Finds the pieces of one color that attack a square, with `blockers` as the occupied squares.
+fn attackers(board: &Board, sq: chess::Square, by: Color, blockers: BitBoard) -> BitBoard {
+    let own = *board.color_combined(by);
+    let queens = *board.pieces(Piece::Queen);
+
+    ((chess::get_rook_moves(sq, blockers) & (*board.pieces(Piece::Rook) | queens))
+        | (chess::get_bishop_moves(sq, blockers) & (*board.pieces(Piece::Bishop) | queens))
+        | (chess::get_knight_moves(sq) & *board.pieces(Piece::Knight))
+        | (chess::get_king_moves(sq) & *board.pieces(Piece::King))
+        | chess::get_pawn_attacks(sq, !by, *board.pieces(Piece::Pawn)))
+        & own
+}
+
+
+/// Highlights, arrows and text badges that can be put on any square from code,
+/// e.g. for lessons. Drawn in its own pass above the pieces.
+#[derive(Clone, Default)]
+struct Overlay {
+    highlights: HashMap<chess::Square, graphics::Color>,
+
+    // From, to, color and shaft width.
+    arrows: Vec<(chess::Square, chess::Square, graphics::Color, f32)>,
+
+    badges: HashMap<chess::Square, String>,
+}
+
+impl Overlay {
+
+    /// Colors a square, replacing any earlier highlight on that square.
+    fn highlight(&mut self, sq: chess::Square, color: graphics::Color) {
+        self.highlights.insert(sq, color);
+    }
+
+    /// Adds an arrow pointing from one square to another.
+    fn arrow(&mut self, from: chess::Square, to: chess::Square, color: graphics::Color) {
+        self.arrows.push((from, to, color, 10.0));
+    }
+
+    /// Adds an arrow with a wider shaft and head, easier to see on a projector.
+    fn thick_arrow(&mut self, from: chess::Square, to: chess::Square, color: graphics::Color) {
+        self.arrows.push((from, to, color, 20.0));
+    }
+
+    /// Puts a short text badge in the corner of a square.
+    fn badge(&mut self, sq: chess::Square, text: &str) {
+        self.badges.insert(sq, text.to_string());
+    }
+
+    /// Removes every highlight, arrow and badge.
+    fn clear(&mut self) {
+        self.highlights.clear();
+        self.arrows.clear();
+        self.badges.clear();
+    }
+
+    /// Draws highlights first, then arrows and last the badges so text stays readable.
+    fn draw(&self, ctx: &mut Context, flipped: bool, font: graphics::Font) -> GameResult {
+        for (sq, color) in self.highlights.iter() {
+            let rectangle = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                square_rect(*sq, flipped),
+                *color,
+            )?;
+            graphics::draw(ctx, &rectangle, graphics::DrawParam::default())?;
+        }
+
+        for (from, to, color, width) in self.arrows.iter() {
+            if from == to {
+                continue;
+            }
+            let start = square_center(*from, flipped);
+            let end = square_center(*to, flipped);
+
+            //direction and normal of the arrow, used to place the head
+            let length = ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2)).sqrt();
+            let dir = [(end[0] - start[0]) / length, (end[1] - start[1]) / length];
+            let normal = [-dir[1], dir[0]];
+            let head_base = [end[0] - dir[0] * 2.5 * width, end[1] - dir[1] * 2.5 * width];
+
+            let shaft = graphics::Mesh::new_line(ctx, &[start, head_base], *width, *color)?;
+            graphics::draw(ctx, &shaft, graphics::DrawParam::default())?;
+
+            let head = graphics::Mesh::new_polygon(
+                ctx,
+                graphics::DrawMode::fill(),
+                &[
+                    end,
+                    [head_base[0] + normal[0] * 1.5 * width, head_base[1] + normal[1] * 1.5 * width],
+                    [head_base[0] - normal[0] * 1.5 * width, head_base[1] - normal[1] * 1.5 * width],
+                ],
+                *color,
+            )?;
+            graphics::draw(ctx, &head, graphics::DrawParam::default())?;
+        }
+
+        for (sq, text) in self.badges.iter() {
+            let badge_text = styled_text(text.as_str(), font, TEXT_SMALL);
+            let dimensions = badge_text.dimensions(ctx);
+            let rect = square_rect(*sq, flipped);
+
+            //badge sits in the top right corner of the square
+            let background = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(
+                    rect.x + rect.w - dimensions.w - 10.0,
+                    rect.y + 2.0,
+                    dimensions.w + 8.0,
+                    dimensions.h + 4.0,
+                ),
+                4.0,
+                BADGE_COLOR,
+            )?;
+            graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+            graphics::draw(
+                ctx,
+                &badge_text,
+                graphics::DrawParam::default().dest([rect.x + rect.w - dimensions.w - 6.0, rect.y + 4.0]),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+
+// Text sizes, everything is scaled further by the UI scale.
+const TEXT_HUGE: f32 = 72.0;
+const TEXT_LARGE: f32 = 30.0;
+const TEXT_MEDIUM: f32 = 25.0;
+const TEXT_NORMAL: f32 = 20.0;
+const TEXT_SMALL: f32 = 15.0;
+
+/// The font bundled in the resources, it covers chess figurines and most alphabets.
+const BUNDLED_FONT: &str = "/DejaVuSans.ttf";
+
+/// Creates a text in a font, `size` should be one of the text sizes.
+fn styled_text(content: &str, font: graphics::Font, size: f32) -> graphics::Text {
+    graphics::Text::new(
+        graphics::TextFragment::from(content)
+            .font(font)
+            .scale(graphics::PxScale { x: size, y: size }),
+    )
+}
+
+/// Loads the font from the settings, falling back to the bundled font, then to the embedded copy of it.
+fn load_font(ctx: &mut Context, settings: &settings::Settings) -> graphics::Font {
+    if let Some(path) = &settings.font {
+        match std::fs::read(path).map_err(GameError::from).and_then(|bytes| graphics::Font::new_glyph_font_bytes(ctx, &bytes)) {
+            Ok(font) => return font,
+            Err(e) => println!("Failed to load font {}: {}", path, e),
+        }
+    }
+
+    graphics::Font::new(ctx, BUNDLED_FONT)
+        .or_else(|_| graphics::Font::new_glyph_font_bytes(ctx, assets::EMBEDDED_FONT))
+        .unwrap_or_else(|e| {
+            println!("Failed to load font {}: {}", BUNDLED_FONT, e);
+            graphics::Font::default()
+        })
+}
+
+/// The modifier of the game shortcuts, Cmd on macOS and Ctrl everywhere else.
+#[cfg(target_os = "macos")]
+const COMMAND_KEY: event::KeyMods = event::KeyMods::LOGO;
+#[cfg(not(target_os = "macos"))]
+const COMMAND_KEY: event::KeyMods = event::KeyMods::CTRL;
+#[cfg(target_os = "macos")]
+const COMMAND_NAME: &str = "Cmd";
+#[cfg(not(target_os = "macos"))]
+const COMMAND_NAME: &str = "Ctrl";
+
+/// Height of the UI scale slider track in the menu.
+const SCALE_SLIDER_Y: f32 = 505.0;
+
+/// How long a firework spark lives, in seconds.
+const PARTICLE_LIFE: f32 = 1.6;
+/// How long the fade after a draw lasts, in seconds.
+const DRAW_FADE_TIME: f32 = 2.5;
+/// How many frame times the stats keep for their percentiles.
+const FRAME_HISTORY: usize = 240;
+/// How long a toast message stays on the board, in seconds.
+const TOAST_TIME: f32 = 1.5;
+/// Seconds between the moves in kiosk mode.
+const KIOSK_MOVE_TIME: f32 = 1.5;
+/// Seconds the final position of a game stays in kiosk mode before the next game.
+const KIOSK_GAME_PAUSE: f32 = 6.0;
+/// Most replays listed under the Replays button, imported databases can have thousands.
+const REPLAY_LIST_ROWS: usize = 14;
+
+/// Small xorshift random number generator, good enough for visual effects and picking positions.
+/// The same seed always gives the same numbers.
+#[derive(Clone)]
+struct Rng(u64);
+
+impl Rng {
+
+    /// Seeds the generator, xorshift needs a state that isn't zero.
+    fn new(seed: u64) -> Rng {
+        Rng(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+
+    /// A seed from the system clock.
+    fn time_seed() -> u64 {
+        time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Seeds the generator from the system clock.
+    fn from_time() -> Rng {
+        Rng::new(Rng::time_seed())
+    }
+
+    /// Returns a random number between 0.0 and 1.0.
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a random index below `len`, which has to be more than 0.
+    fn below(&mut self, len: usize) -> usize {
+        ((self.next_f32() * len as f32) as usize).min(len - 1)
+    }
+}
+
+/// A single spark of the game over effect.
+#[derive(Clone)]
+struct Particle {
+    pos: [f32; 2],
+
+    vel: [f32; 2],
+
+    color: graphics::Color,
+    // Seconds left before the spark is gone.
+    life: f32,
+}
+
+/// Short effect over the board when a game ends, fireworks for a win and a fade for a draw.
+#[derive(Clone)]
+struct Celebration {
+    particles: Vec<Particle>,
+    // Seconds left of the draw fade, 0.0 when not fading.
+    fade: f32,
+
+    rng: Rng,
+}
+
+impl Celebration {
+
+    fn new() -> Celebration {
+        Celebration {
+            particles: vec![],
+            fade: 0.0,
+            rng: Rng::from_time(),
+        }
+    }
+
+    /// Starts a few bursts of sparks at random places over the board.
+    fn fireworks(&mut self) {
+        let board_size = GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+
+        for _ in 0..5 {
+            let center = [
+                20.0 + board_size * (0.2 + 0.6 * self.rng.next_f32()),
+                20.0 + board_size * (0.2 + 0.5 * self.rng.next_f32()),
+            ];
+            let color = graphics::Color::new(
+                0.5 + 0.5 * self.rng.next_f32(),
+                0.5 + 0.5 * self.rng.next_f32(),
+                0.5 + 0.5 * self.rng.next_f32(),
+                1.0,
+            );
+
+            for _ in 0..40 {
+                let angle = self.rng.next_f32() * std::f32::consts::PI * 2.0;
+                let speed = 80.0 + 180.0 * self.rng.next_f32();
+                self.particles.push(Particle {
+                    pos: center,
+                    vel: [angle.cos() * speed, angle.sin() * speed],
+                    color,
+                    life: PARTICLE_LIFE * (0.6 + 0.4 * self.rng.next_f32()),
+                });
+            }
+        }
+    }
+
+    /// Starts the subtle fade shown after a draw.
+    fn fade(&mut self) {
+        self.fade = DRAW_FADE_TIME;
+    }
+
+    /// Stops any running effect.
+    fn clear(&mut self) {
+        self.particles.clear();
+        self.fade = 0.0;
+    }
+
+    /// Moves the sparks and counts down the fade, dt in seconds.
+    fn update(&mut self, dt: f32) {
+        for particle in self.particles.iter_mut() {
+            particle.vel[1] += 250.0 * dt; // gravity
+            particle.pos[0] += particle.vel[0] * dt;
+            particle.pos[1] += particle.vel[1] * dt;
+            particle.life -= dt;
+        }
+        self.particles.retain(|particle| particle.life > 0.0);
+        self.fade = (self.fade - dt).max(0.0);
+    }
+
+    fn draw(&self, ctx: &mut Context) -> GameResult {
+        if self.fade > 0.0 {
+            //fades in and then out again over the board
+            let alpha = 0.45 * (std::f32::consts::PI * self.fade / DRAW_FADE_TIME).sin();
+            let board_size = GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+            let shade = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(20.0, 20.0, board_size, board_size),
+                graphics::Color::new(0.0, 0.0, 0.0, alpha),
+            )?;
+            graphics::draw(ctx, &shade, graphics::DrawParam::default())?;
+        }
+
+        for particle in self.particles.iter() {
+            let color = graphics::Color::new(
+                particle.color.r,
+                particle.color.g,
+                particle.color.b,
+                (particle.life / PARTICLE_LIFE).min(1.0),
+            );
+            let spark = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                particle.pos,
+                4.0,
+                0.5,
+                color,
+            )?;
+            graphics::draw(ctx, &spark, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Board look, i.e. optional textures under the tiles and an optional window background.
+#[derive(Clone)]
+struct Theme {
+    // Index into BOARD_TEXTURES, None means plain colored tiles.
+    texture: Option<usize>,
+
+    light_texture: Option<graphics::Image>,
+
+    dark_texture: Option<graphics::Image>,
+
+    background: Option<graphics::Image>,
+    // How strongly the tile colors are drawn on top of a texture, 0.0 to 1.0.
+    tint: f32,
+}
+
+impl Theme {
+
+    /// Plain colored tiles, with "/background.png" as window background if it exists.
+    fn new(ctx: &mut Context) -> Theme {
+        Theme {
+            texture: None,
+            light_texture: None,
+            dark_texture: None,
+            background: graphics::Image::new(ctx, "/background.png").ok(),
+            tint: 0.4,
+        }
+    }
+
+    /// Switches to the next board texture, going back to plain tiles after the last one.
+    fn next_texture(&mut self, ctx: &mut Context) {
+        let next = match self.texture {
+            None => Some(0),
+            Some(i) if i + 1 < BOARD_TEXTURES.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        self.set_texture(ctx, next);
+    }
+
+    /// Uses a board texture, an index into BOARD_TEXTURES or None for plain tiles.
+    fn set_texture(&mut self, ctx: &mut Context, texture: Option<usize>) {
+        self.texture = texture;
+        match self.texture {
+            Some(i) => {
+                self.light_texture = graphics::Image::new(ctx, BOARD_TEXTURES[i].1).ok();
+                self.dark_texture = graphics::Image::new(ctx, BOARD_TEXTURES[i].2).ok();
+                println!("Board texture: {}", BOARD_TEXTURES[i].0);
+            }
+            None => {
+                self.light_texture = None;
+                self.dark_texture = None;
+                println!("Board texture: None");
+            }
+        }
+    }
+
+    /// Name of the board texture, None for plain tiles.
+    fn texture_name(&self) -> Option<String> {
+        self.texture.map(|i| BOARD_TEXTURES[i].0.to_string())
+    }
+
+    /// Changes the tint by the given amount, kept between 0.0 and 1.0.
+    fn adjust_tint(&mut self, amount: f32) {
+        self.tint = (self.tint + amount).max(0.0).min(1.0);
+    }
+
+    /// Color to draw a tile with, see-through when there is a texture under it.
+    fn tile_color(&self, color: graphics::Color) -> graphics::Color {
+        if self.light_texture.is_some() && self.dark_texture.is_some() {
+            graphics::Color::new(color.r, color.g, color.b, self.tint)
+        } else {
+            color
+        }
+    }
+}
+
+
+/// Who plays one side, shown on the name plates next to the board.
+#[derive(Clone)]
+struct Player {
+    name: String,
+
+    rating: Option<u32>,
+
+    avatar: Option<graphics::Image>,
+    // Index of the profile playing this side, if any.
+    profile: Option<usize>,
+}
+
+impl Player {
+
+    /// A player without profile, rating or avatar.
+    fn new(name: &str) -> Player {
+        Player {
+            name: name.to_string(),
+            rating: None,
+            avatar: None,
+            profile: None,
+        }
+    }
+}
+
+
+/// Running score between two players over consecutive games in a session, in half points.
+#[derive(Clone)]
+struct MatchScore {
+    names: [String; 2],
+
+    halves: [u32; 2],
+}
+
+impl MatchScore {
+
+    fn new(first: &str, second: &str) -> MatchScore {
+        MatchScore {
+            names: [first.to_string(), second.to_string()],
+            halves: [0, 0],
+        }
+    }
+
+    /// Adds a finished game, `winner` is the name of the winner or None for a draw.
+    fn add(&mut self, winner: Option<&str>) {
+        for i in 0..2 {
+            match winner {
+                Some(name) if name == self.names[i] => self.halves[i] += 2,
+                Some(_) => {}
+                None => self.halves[i] += 1,
+            }
+        }
+    }
+
+    /// True when the score is between these two players, on any side.
+    fn is_between(&self, first: &str, second: &str) -> bool {
+        (self.names[0] == first && self.names[1] == second) || (self.names[0] == second && self.names[1] == first)
+    }
+
+    /// Points of a player, None if the player is not part of the match.
+    fn points_of(&self, name: &str) -> Option<String> {
+        self.names.iter().position(|n| n == name).map(|i| MatchScore::points(self.halves[i]))
+    }
+
+    /// Writes half points the way chess scores are written, e.g. 2½.
+    fn points(halves: u32) -> String {
+        match (halves / 2, halves % 2) {
+            (0, 1) => "½".to_string(),
+            (whole, 1) => format!("{}½", whole),
+            (whole, _) => whole.to_string(),
+        }
+    }
+}
+
+
+/// Questions that have to be answered with Y or N before the game goes on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Confirm {
+    Resign,
+
+    OfferDraw,
+    // The opponent answers a draw offer.
+    AcceptDraw,
+}
+
+
+/// Kiosk mode plays the replays one after another on the analysis board, in a loop.
+#[derive(Clone, Copy)]
+struct Kiosk {
+    // Index of the replay being played.
+    game: usize,
+    // Moves played of it, None until its start position is set up.
+    ply: Option<usize>,
+    // Seconds until the next step.
+    wait: f32,
+}
+
+
+/// Presentation mode for teaching on a projector: a fullscreen board without the side panel,
+/// marks and arrows put with the keyboard and a laser pointer dot at the mouse cursor.
+#[derive(Clone)]
+struct Presentation {
+    // Marked squares of the teacher, kept until they are cleared.
+    marks: Vec<chess::Square>,
+
+    arrows: Vec<(chess::Square, chess::Square)>,
+    // Square of the keyboard cursor.
+    cursor: chess::Square,
+    // Start of an arrow, it ends where Enter is pressed the second time.
+    arrow_from: Option<chess::Square>,
+    // Steps recorded for a lesson, saved when presentation mode ends.
+    steps: Vec<lesson::Step>,
+    // Note of the step being recorded, typing goes to it until Enter.
+    note: Option<String>,
+    // False until the first typed character, the N that started the note also arrives as text.
+    typing: bool,
+}
+
+/// A lesson a student goes through step by step.
+#[derive(Clone)]
+struct LessonView {
+    steps: Vec<lesson::Step>,
+
+    step: usize,
+}
+
+/// The marks and arrows of presentation mode and lessons, in colors that are seen on a projector.
+fn annotation_overlay(marks: &[chess::Square], arrows: &[(chess::Square, chess::Square)]) -> Overlay {
+    let mut overlay = Overlay::default();
+    for mark in marks.iter() {
+        overlay.highlight(*mark, graphics::Color::new(0.95, 0.1, 0.1, 0.55));
+    }
+    for (from, to) in arrows.iter() {
+        overlay.thick_arrow(*from, *to, graphics::Color::new(1.0, 0.65, 0.0, 0.95));
+    }
+    overlay
+}
+
+
+/// Steps of the setup shown the first time the game starts, each changes one setting.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SetupStep {
+    Texture,
+
+    SquareNames,
+
+    TouchMove,
+}
+
+impl SetupStep {
+
+    /// The step after this one, None after the last.
+    fn next(self) -> Option<SetupStep> {
+        match self {
+            SetupStep::Texture => Some(SetupStep::SquareNames),
+            SetupStep::SquareNames => Some(SetupStep::TouchMove),
+            SetupStep::TouchMove => None,
+        }
+    }
+
+    fn number(self) -> usize {
+        match self {
+            SetupStep::Texture => 1,
+            SetupStep::SquareNames => 2,
+            SetupStep::TouchMove => 3,
+        }
+    }
+}
+
+
+/// Where a key binding works, the cheat sheet groups the bindings by it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum KeyContext {
+    Everywhere,
+    // A running game, not training, a puzzle, a drill or the analysis board.
+    Game,
+    // No game is running, and the side panel is shown.
+    Menu,
+
+    Replay,
+
+    Analysis,
+
+    Presentation,
+    // A lesson is open.
+    Lesson,
+}
+
+impl KeyContext {
+
+    fn name(self) -> &'static str {
+        match self {
+            KeyContext::Everywhere => "Everywhere",
+            KeyContext::Game => "Game",
+            KeyContext::Menu => "Menu",
+            KeyContext::Replay => "Replay",
+            KeyContext::Analysis => "Analysis",
+            KeyContext::Presentation => "Presentation",
+            KeyContext::Lesson => "Lesson",
+        }
+    }
+}
+
+/// Something a key does.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Action {
+    Resign,
+    OfferDraw,
+    ClaimDraw,
+    ReplayForward,
+    ReplayBack,
+    NextTexture,
+    LessTint,
+    MoreTint,
+    ToggleArrows,
+    StartVision,
+    ToggleMobility,
+    TogglePositionalHints,
+    ToggleAutoFlip,
+    SlowerFlip,
+    FasterFlip,
+    TakeBack,
+    ShowPosition,
+    CloseAnalysis,
+    MarkPosition,
+    Compare,
+    StartPuzzle,
+    PreviousTheme,
+    NextTheme,
+    StartTraining,
+    SearchReplays,
+    NextUser,
+    RecordPuzzle,
+    ToggleStats,
+    ToggleVsync,
+    NextFpsCap,
+    ToggleTouchMove,
+    ToggleSquareNames,
+    ToggleEffects,
+    ToggleKeySheet,
+    TogglePresentation,
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    MarkSquare,
+    DrawArrow,
+    ClearAnnotations,
+    RecordStep,
+    PreviousStep,
+    NextStep,
+    CloseLesson,
+}
+
+/// A key, with or without the command key, and what it does where.
+struct KeyBinding {
+    key: event::KeyCode,
+
+    command: bool,
+
+    context: KeyContext,
+
+    action: Action,
+    // How the key is written on the cheat sheet, without the command key.
+    name: &'static str,
+
+    description: &'static str,
+}
+
+/// Every key binding, key_down_event and the cheat sheet both go by this list.
+const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding { key: event::KeyCode::Slash, command: false, context: KeyContext::Everywhere, action: Action::ToggleKeySheet, name: "?", description: "Show or hide this cheat sheet" },
+    KeyBinding { key: event::KeyCode::T, command: false, context: KeyContext::Everywhere, action: Action::NextTexture, name: "T", description: "Switch the board texture" },
+    KeyBinding { key: event::KeyCode::LBracket, command: false, context: KeyContext::Everywhere, action: Action::LessTint, name: "[", description: "Less tile color over the texture" },
+    KeyBinding { key: event::KeyCode::RBracket, command: false, context: KeyContext::Everywhere, action: Action::MoreTint, name: "]", description: "More tile color over the texture" },
+    KeyBinding { key: event::KeyCode::H, command: false, context: KeyContext::Everywhere, action: Action::ToggleArrows, name: "H", description: "Attack arrows on or off" },
+    KeyBinding { key: event::KeyCode::K, command: false, context: KeyContext::Menu, action: Action::StartVision, name: "K", description: "Vision drill: find every check and capture" },
+    KeyBinding { key: event::KeyCode::I, command: false, context: KeyContext::Everywhere, action: Action::ToggleMobility, name: "I", description: "Mobility hint on or off" },
+    KeyBinding { key: event::KeyCode::J, command: false, context: KeyContext::Everywhere, action: Action::TogglePositionalHints, name: "J", description: "Open files and outposts on or off" },
+    KeyBinding { key: event::KeyCode::F, command: false, context: KeyContext::Everywhere, action: Action::ToggleAutoFlip, name: "F", description: "Auto-flip on or off" },
+    KeyBinding { key: event::KeyCode::Comma, command: false, context: KeyContext::Everywhere, action: Action::SlowerFlip, name: ",", description: "Slower board flip" },
+    KeyBinding { key: event::KeyCode::Period, command: false, context: KeyContext::Everywhere, action: Action::FasterFlip, name: ".", description: "Faster board flip" },
+    KeyBinding { key: event::KeyCode::M, command: false, context: KeyContext::Everywhere, action: Action::MarkPosition, name: "M", description: "Mark the shown position" },
+    KeyBinding { key: event::KeyCode::C, command: false, context: KeyContext::Everywhere, action: Action::Compare, name: "C", description: "Compare with the marked position" },
+    KeyBinding { key: event::KeyCode::P, command: false, context: KeyContext::Everywhere, action: Action::RecordPuzzle, name: "P", description: "Record a puzzle, press again to save it" },
+    KeyBinding { key: event::KeyCode::F3, command: false, context: KeyContext::Everywhere, action: Action::ToggleStats, name: "F3", description: "Frame stats" },
+    KeyBinding { key: event::KeyCode::V, command: false, context: KeyContext::Everywhere, action: Action::ToggleVsync, name: "V", description: "Vsync on or off" },
+    KeyBinding { key: event::KeyCode::L, command: false, context: KeyContext::Everywhere, action: Action::NextFpsCap, name: "L", description: "Next frame limit" },
+    KeyBinding { key: event::KeyCode::O, command: false, context: KeyContext::Everywhere, action: Action::ToggleTouchMove, name: "O", description: "Touch-move rule on or off" },
+    KeyBinding { key: event::KeyCode::S, command: false, context: KeyContext::Everywhere, action: Action::ToggleSquareNames, name: "S", description: "Square names on or off" },
+    KeyBinding { key: event::KeyCode::E, command: false, context: KeyContext::Everywhere, action: Action::ToggleEffects, name: "E", description: "Game over effects on or off" },
+    KeyBinding { key: event::KeyCode::W, command: false, context: KeyContext::Everywhere, action: Action::TogglePresentation, name: "W", description: "Presentation mode on or off" },
+    KeyBinding { key: event::KeyCode::R, command: true, context: KeyContext::Game, action: Action::Resign, name: "R", description: "Resign" },
+    KeyBinding { key: event::KeyCode::D, command: true, context: KeyContext::Game, action: Action::OfferDraw, name: "D", description: "Offer a draw" },
+    KeyBinding { key: event::KeyCode::C, command: true, context: KeyContext::Game, action: Action::ClaimDraw, name: "C", description: "Claim a draw by repetition or the fifty-move rule" },
+    KeyBinding { key: event::KeyCode::U, command: false, context: KeyContext::Menu, action: Action::StartPuzzle, name: "U", description: "Start a puzzle" },
+    KeyBinding { key: event::KeyCode::Left, command: false, context: KeyContext::Menu, action: Action::PreviousTheme, name: "Left", description: "Previous puzzle theme" },
+    KeyBinding { key: event::KeyCode::Right, command: false, context: KeyContext::Menu, action: Action::NextTheme, name: "Right", description: "Next puzzle theme" },
+    KeyBinding { key: event::KeyCode::G, command: false, context: KeyContext::Menu, action: Action::StartTraining, name: "G", description: "Guess-the-move training on the last game" },
+    KeyBinding { key: event::KeyCode::F, command: true, context: KeyContext::Menu, action: Action::SearchReplays, name: "F", description: "Search the replays by player, date, result or opening" },
+    KeyBinding { key: event::KeyCode::U, command: true, context: KeyContext::Menu, action: Action::NextUser, name: "U", description: "Sign in as the next profile, with its own settings and replays" },
+    KeyBinding { key: event::KeyCode::D, command: false, context: KeyContext::Replay, action: Action::ReplayForward, name: "D", description: "Next position" },
+    KeyBinding { key: event::KeyCode::A, command: false, context: KeyContext::Replay, action: Action::ReplayBack, name: "A", description: "Previous position" },
+    KeyBinding { key: event::KeyCode::Back, command: false, context: KeyContext::Analysis, action: Action::TakeBack, name: "Backspace", description: "Take back a move" },
+    KeyBinding { key: event::KeyCode::X, command: false, context: KeyContext::Analysis, action: Action::ShowPosition, name: "X", description: "Show the FEN and PGN" },
+    KeyBinding { key: event::KeyCode::Escape, command: false, context: KeyContext::Analysis, action: Action::CloseAnalysis, name: "Escape", description: "Close the analysis board" },
+    KeyBinding { key: event::KeyCode::Up, command: false, context: KeyContext::Presentation, action: Action::CursorUp, name: "Up", description: "Move the square cursor up" },
+    KeyBinding { key: event::KeyCode::Down, command: false, context: KeyContext::Presentation, action: Action::CursorDown, name: "Down", description: "Move the square cursor down" },
+    KeyBinding { key: event::KeyCode::Left, command: false, context: KeyContext::Presentation, action: Action::CursorLeft, name: "Left", description: "Move the square cursor left" },
+    KeyBinding { key: event::KeyCode::Right, command: false, context: KeyContext::Presentation, action: Action::CursorRight, name: "Right", description: "Move the square cursor right" },
+    KeyBinding { key: event::KeyCode::Space, command: false, context: KeyContext::Presentation, action: Action::MarkSquare, name: "Space", description: "Mark or unmark the square" },
+    KeyBinding { key: event::KeyCode::Return, command: false, context: KeyContext::Presentation, action: Action::DrawArrow, name: "Enter", description: "Start an arrow, Enter again ends it" },
+    KeyBinding { key: event::KeyCode::Delete, command: false, context: KeyContext::Presentation, action: Action::ClearAnnotations, name: "Delete", description: "Clear the marks and arrows" },
+    KeyBinding { key: event::KeyCode::N, command: false, context: KeyContext::Presentation, action: Action::RecordStep, name: "N", description: "Record a lesson step, type its note" },
+    KeyBinding { key: event::KeyCode::Left, command: false, context: KeyContext::Lesson, action: Action::PreviousStep, name: "Left", description: "Previous lesson step" },
+    KeyBinding { key: event::KeyCode::Right, command: false, context: KeyContext::Lesson, action: Action::NextStep, name: "Right", description: "Next lesson step" },
+    KeyBinding { key: event::KeyCode::Escape, command: false, context: KeyContext::Lesson, action: Action::CloseLesson, name: "Escape", description: "Close the lesson" },
+];
+
+
+/// A puzzle that waits for a title, themes and difficulty before it is saved.
+#[derive(Clone)]
+struct PuzzleDraft {
+    puzzle: puzzles::Puzzle,
+
+    difficulty: puzzles::Difficulty,
+    // Themes typed with spaces between them.
+    themes: String,
+    // Typing goes to the themes instead of the title, Tab switches.
+    editing_themes: bool,
+    // False until the first typed character, the P that opened the dialog also arrives as text.
+    typing: bool,
+}
+
+impl PuzzleDraft {
+
+    /// The text field that typing goes to.
+    fn field(&mut self) -> &mut String {
+        if self.editing_themes { &mut self.themes } else { &mut self.puzzle.title }
+    }
+}
+
+
+/// A puzzle being solved, the other side's moves are played automatically.
+#[derive(Clone)]
+struct PuzzlePlay {
+    puzzle: puzzles::Puzzle,
+    // Index of the next move of the solution.
+    step: usize,
+
+    mistakes: u32,
+}
+
+
+/// GUI logic and event implementation structure.
+#[derive(Clone)]
+struct AppState {
+    sprites: HashMap<(Color, Piece), graphics::Image>,
+    // Reads the piece images in the background, the splash screen is shown until it is done.
+    loading: Option<assets::AssetLoader>,
+    // Example board representation.
+    board: Board,
+    // Imported game representation.
+    status: BoardStatus,
+
+    game: Game,
+
+    side_to_move: Color,
+
+    pos_x: f32,
+    
+    pos_y: f32,
+    // Where the mouse picked up the piece, for the slip protection distance.
+    grab_point: [f32; 2],
+    // From and to square of a promotion waiting for the piece to be picked.
+    promotion_choice: Option<(chess::Square, chess::Square)>,
+
+    piece: (Option<Color>, Option<Piece>),
+
+    saved_replay: Vec<replay::Replay>,
+
+    // the replay opened with the Replays button
+    replay_view: Option<replay::Replay>,
+
+    // The words typed to filter the replay list, None when it isn't searched.
+    replay_search: Option<String>,
+
+    replay_boards: Vec<Board>,
+
+    replay_turn: usize,
+    // The position the running game started from.
+    start_position: Board,
+
+    // Deepest known opening the running game has gone through.
+    opening: Option<eco::Opening>,
+
+    // Highlights, arrows and badges drawn above the pieces.
+    overlay: Overlay,
+    // Setting for showing which enemy pieces attack the squares a grabbed piece can go to.
+    attack_arrows: bool,
+    // Beginner hint with the number of legal moves on every piece of the side to move.
+    mobility_hint: bool,
+    // The mobility badges and the position they were counted for.
+    mobility: Option<(Board, Overlay)>,
+    // Teaching hint with the open files, weak squares and outposts of the side to move.
+    positional_hints: bool,
+    // Black is at the bottom of the board when true.
+    flipped: bool,
+    // Setting for turning the board towards the side to move after every move.
+    auto_flip: bool,
+    // Setting for how long turning the board takes, in seconds.
+    flip_duration: f32,
+    // Seconds left of the running flip animation.
+    flip_timer: f32,
+
+    theme: Theme,
+
+    celebration: Celebration,
+    // Setting for the game over effects.
+    effects: bool,
+    // Open confirmation dialog, if any.
+    confirm: Option<Confirm>,
+    // A FEN from another program that was refused, with everything wrong with it. No game starts while it is shown.
+    position_problems: Option<(String, Vec<String>)>,
+    // How the last game ended, shown in the menu.
+    result: Option<String>,
+    // The players of White and Black, indexed by Color::to_index.
+    players: [Player; 2],
+
+    profiles: Vec<profiles::Profile>,
+    // Where the profiles are saved, in the user config directory.
+    profiles_path: path::PathBuf,
+    // Score between the two players of the last games.
+    match_score: Option<MatchScore>,
+    // The UI scale slider follows the mouse until the button is let go.
+    dragging_scale: bool,
+
+    settings: settings::Settings,
+
+    font: graphics::Font,
+    // Only the board, name plates and last move are drawn, on a chroma key background.
+    stream_mode: bool,
+    // Commands from other programs, carried out in update().
+    inbox: remote::Inbox,
+    // The running game shared with the API server, when it runs.
+    shared_state: Option<remote::SharedState>,
+    // F3 shows the frame rate and how many draw calls the board took.
+    show_stats: bool,
+    // The key binding cheat sheet is open.
+    show_keys: bool,
+    // Step of the first-run setup, None when it is done.
+    setup: Option<SetupStep>,
+    // Read-only demo that plays the replays in a loop, only Escape works.
+    kiosk: Option<Kiosk>,
+
+    presentation: Option<Presentation>,
+
+    lesson_view: Option<LessonView>,
+    // Recorded lessons are saved here.
+    lessons_dir: path::PathBuf,
+
+    board_draws: u32,
+    // Frame times of the last frames in milliseconds, for the percentiles in the stats.
+    frame_times: std::collections::VecDeque<f32>,
+    // When the mouse let go of a dragged piece, and how long it took from there until the move was played.
+    drop_time: Option<Instant>,
+
+    move_latency: Option<Duration>,
+    // Where the settings are saved, in the user config directory.
+    settings_path: path::PathBuf,
+    // Profiles, settings, puzzles and the journal are kept here.
+    data_dir: path::PathBuf,
+    // The profile whose settings and replays are in use, None for the shared ones.
+    user: Option<usize>,
+    // Replays of the users that aren't signed in, so they stay apart on a shared machine.
+    user_replays: HashMap<Option<usize>, Vec<replay::Replay>>,
+    // When the last frame started, for the frame limiter.
+    last_frame: Instant,
+    // Guess-the-move training on a saved game, kept after it ends for its summary.
+    training: Option<training::GuessTraining>,
+    // Where the puzzles are saved, in the user config directory.
+    puzzles_path: path::PathBuf,
+    // Position and number of played moves when recording a puzzle solution started.
+    puzzle_start: Option<(Board, usize)>,
+    // Open dialog for naming a recorded puzzle.
+    puzzle_draft: Option<PuzzleDraft>,
+    // Progress of a running puzzle import.
+    puzzle_import: Option<puzzles::SharedProgress>,
+    // Journal the moves of the running game are written to as they are played.
+    journal_path: path::PathBuf,
+    // Progress of a running PGN game import.
+    game_import: Option<import::SharedImport>,
+    // Keys of the imported games, so importing them again skips them.
+    imported_games: HashSet<String>,
+    // All saved puzzles, loaded again after puzzles are added.
+    puzzle_db: Vec<puzzles::Puzzle>,
+    // Only puzzles with this theme are picked, all puzzles when None.
+    puzzle_theme: Option<String>,
+
+    puzzle_play: Option<PuzzlePlay>,
+    // A free board where both sides move, moves can be taken back and nothing is recorded.
+    analysis: bool,
+    // The FEN and PGN of the analysis board are shown next to it, toggled with X.
+    show_position: bool,
+    // Position marked with M, compared side by side with the shown one when `comparing`.
+    marked_position: Option<Board>,
+    // Whether the window has focus, moves from other programs are notified when it doesn't.
+    focused: bool,
+    // A remote move to notify about in the next update.
+    move_notification: Option<String>,
+    // Short message shown at the bottom of the board and the seconds it has left.
+    toast: Option<(String, f32)>,
+    // The side to move has claimed a draw with the move it plays next.
+    draw_claim: bool,
+    // The piece that was touched and has to be moved, with touch-move on.
+    touched: Option<chess::Square>,
+    // The running check and capture drill.
+    vision: Option<vision::VisionDrill>,
+    // Seed of `rng`, printed at startup and set with --seed so a session can be played again.
+    seed: u64,
+    // Picks puzzles and vision positions, the effects have their own generator.
+    rng: Rng,
+
+    comparing: bool,
+
+}
+
+impl AppState {
+
+    /// Initialise new application, i.e. initialise new game and load resources.
+    fn new(ctx: &mut Context) -> GameResult<AppState> {
+        
+        //profiles, settings, puzzles and the journal are beside the executable in portable mode
+        let data_dir = portable_dir().unwrap_or_else(|| ggez::filesystem::user_config_dir(ctx).to_path_buf());
+
+        //loads the profiles, or creates one for each side the first time
+        let profiles_path = data_dir.join("profiles.txt");
+        let mut profiles = profiles::load(&profiles_path);
+        if profiles.is_empty() {
+            profiles = vec![
+                profiles::Profile::new("Player 1", Some(Color::White)),
+                profiles::Profile::new("Player 2", Some(Color::Black)),
+            ];
+            if let Err(e) = profiles::save(&profiles_path, &profiles) {
+                println!("Failed to save profiles: {}", e);
+            }
+        }
+
+        //loads the settings, and writes the defaults the first time so they can be edited
+        let settings_path = data_dir.join("settings.txt");
+        let first_run = !settings_path.exists();
+        if first_run {
+            if let Err(e) = settings::Settings::default().save(&settings_path) {
+                println!("Failed to save settings: {}", e);
+            }
+        }
+        let settings = settings::Settings::load(&settings_path);
+
+        let mut state = AppState {
+            sprites: HashMap::new(),
+            loading: Some(assets::AssetLoader::start(
+                SPRITE_FILES.iter().map(|(_, name)| name.to_string()).collect(),
+                vec![resources_dir().join(PIECES_DIR)],
+            )),
+            board:  Board::default(),
+            status: BoardStatus::Checkmate,
+            game: Game::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").expect("Valid FEN"),
+            side_to_move: Color::White,
+            pos_x: 355.0,
+            pos_y: 355.0,
+            grab_point: [0.0, 0.0],
+            promotion_choice: None,
+            piece: (None, None),
+            saved_replay: vec![],
+            replay_view: None,
+            replay_search: None,
+            replay_boards: vec![Board::default()],
+            replay_turn: 999,
+            start_position: Board::default(),
+            opening: None,
+            overlay: Overlay::default(),
+            attack_arrows: false,
+            mobility_hint: false,
+            mobility: None,
+            positional_hints: false,
+            flipped: false,
+            auto_flip: false,
+            flip_duration: 0.4,
+            flip_timer: 0.0,
+            theme: Theme::new(ctx),
+            celebration: Celebration::new(),
+            effects: true,
+            confirm: None,
+            position_problems: None,
+            result: None,
+            players: [Player::new("White"), Player::new("Black")],
+            profiles,
+            profiles_path,
+            match_score: None,
+            dragging_scale: false,
+            font: load_font(ctx, &settings),
+            stream_mode: settings.stream_mode,
+            inbox: remote::Inbox::default(),
+            shared_state: None,
+            show_stats: false,
+            show_keys: false,
+            board_draws: 0,
+            frame_times: std::collections::VecDeque::with_capacity(FRAME_HISTORY),
+            drop_time: None,
+            move_latency: None,
+            settings_path,
+            data_dir: data_dir.clone(),
+            user: None,
+            user_replays: HashMap::new(),
+            last_frame: Instant::now(),
+            training: None,
+            puzzles_path: data_dir.join("puzzles.txt"),
+            puzzle_start: None,
+            puzzle_draft: None,
+            puzzle_import: None,
+            journal_path: data_dir.join("journal.txt"),
+            game_import: None,
+            imported_games: HashSet::new(),
+            puzzle_db: vec![],
+            puzzle_theme: None,
+            puzzle_play: None,
+            analysis: false,
+            show_position: false,
+            marked_position: None,
+            focused: true,
+            move_notification: None,
+            toast: None,
+            draw_claim: false,
+            touched: None,
+            vision: None,
+            seed: 0,
+            rng: Rng::new(0),
+            comparing: false,
+            setup: if first_run { Some(SetupStep::Texture) } else { None },
+            kiosk: None,
+            presentation: None,
+            lesson_view: None,
+            lessons_dir: data_dir.join("lessons"),
+            settings,
+        };
+
+        state.set_ui_scale(ctx, state.settings.ui_scale)?;
+
+        let texture = state.settings.texture.as_ref().and_then(|name| BOARD_TEXTURES.iter().position(|(texture, _, _)| *texture == name.as_str()));
+        if texture.is_some() {
+            state.theme.set_texture(ctx, texture);
+        }
+
+        state.puzzle_db = puzzles::load(&state.puzzles_path);
+
+        //each side starts with the first profile that prefers it
+        for color in chess::ALL_COLORS.iter() {
+            let index = state.profiles.iter().position(|profile| profile.preferred_color == Some(*color));
+            state.select_profile(ctx, *color, index);
+        }
+
+        Ok(state)
+    }
+
+    /// Starts the session's random numbers over from a seed.
+    fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+        println!("Session seed: {} (start with --seed {} to get the same puzzles and drills)", seed, seed);
+    }
+
+    /// Starts turning the board so that Black ends up at the bottom when `flipped` is true.
+    fn set_flipped(&mut self, flipped: bool) {
+        if self.flipped != flipped {
+            self.flipped = flipped;
+            self.flip_timer = self.flip_duration;
+        }
+    }
+
+    /// The orientation the board is drawn in, halfway through a flip it switches to the new one.
+    fn view_flipped(&self) -> bool {
+        if self.flip_timer > self.flip_duration / 2.0 { !self.flipped } else { self.flipped }
+    }
+
+    /// Vertical scale of the board while it is flipping, 1.0 when it is still.
+    fn flip_scale(&self) -> f32 {
+        if self.flip_timer <= 0.0 || self.flip_duration <= 0.0 {
+            return 1.0;
+        }
+        (std::f32::consts::PI * self.flip_timer / self.flip_duration).cos().abs()
+    }
+
+    /// Puts a profile, or no profile, on one side and shows it on its name plate.
+    fn select_profile(&mut self, ctx: &mut Context, color: Color, index: Option<usize>) {
+        let player = match index.and_then(|i| self.profiles.get(i)) {
+            Some(profile) => Player {
+                name: profile.name.clone(),
+                rating: profile.rating,
+                avatar: profile.avatar.as_ref().and_then(|path| graphics::Image::new(ctx, path).ok()),
+                profile: index,
+            },
+            None => Player::new(&format!("{:?}", color)),
+        };
+        self.players[color.to_index()] = player;
+    }
+
+    /// Picks the next profile for one side, after the last profile the side gets no profile.
+    fn next_profile(&mut self, ctx: &mut Context, color: Color) {
+        let next = match self.players[color.to_index()].profile {
+            None if !self.profiles.is_empty() => Some(0),
+            Some(i) if i + 1 < self.profiles.len() => Some(i + 1),
+            _ => None,
+        };
+        self.select_profile(ctx, color, next);
+    }
+
+    /// Ends the running game, saves it for replays and shows the result in the menu.
+    /// `winner` is None for a draw.
+    fn end_game(&mut self, winner: Option<Color>, result: String) {
+        println!("{}", result);
+        self.status = BoardStatus::Checkmate;
+        //a promotion picker left open can't promote in a finished game
+        self.promotion_choice = None;
+        //the result of a game ended on time or by agreement isn't in the chess game, so it comes from the winner
+        let mut info = replay::GameInfo::from_pgn(&self.game_pgn());
+        info.result = match winner {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }.to_string();
+        self.saved_replay.push(replay::Replay::new(self.start_position, &self.played_moves()).with_info(info));
+        if let Err(e) = journal::finish(&self.journal_path, &result) {
+            println!("Failed to write the journal: {}", e);
+        }
+        self.result = Some(result);
+        self.record_result(winner);
+
+        //adds the game to the match score, a new match starts when someone else plays
+        let white = self.players[Color::White.to_index()].name.clone();
+        let black = self.players[Color::Black.to_index()].name.clone();
+        let winner_name = winner.map(|color| self.players[color.to_index()].name.clone());
+        if !self.match_score.as_ref().map_or(false, |score| score.is_between(&white, &black)) {
+            self.match_score = Some(MatchScore::new(&white, &black));
+        }
+        if let Some(score) = &mut self.match_score {
+            score.add(winner_name.as_deref());
+        }
+
+        self.game_changed();
+    }
+
+    /// Sets up the board for a new game with the players on their current sides.
+    fn new_game(&mut self) {
+        if self.position_problems.is_some() {
+            return;
+        }
+        self.start_game(Board::default());
+    }
+
+    /// Starts a game from a position, the side to move in it moves first.
+    fn start_game(&mut self, board: Board) {
+        self.board = board;
+        self.start_position = board;
+        self.opening = None;
+        self.lesson_view = None;
+        self.status = BoardStatus::Ongoing;
+        self.game = Game::new_with_board(board);
+        self.side_to_move = board.side_to_move();
+        self.piece = (None, None);
+        self.replay_boards.clear();
+        self.replay_boards.push(board);
+        self.replay_turn = 999;
+        self.result = None;
+        self.training = None;
+        self.puzzle_start = None;
+        self.puzzle_play = None;
+        self.analysis = false;
+        self.vision = None;
+        self.draw_claim = false;
+        self.touched = None;
+        self.promotion_choice = None;
+        self.replay_search = None;
+        if self.auto_flip { self.set_flipped(self.side_to_move == Color::Black); }
+        self.game_changed();
+    }
+
+    /// Opens the analysis board at the starting position.
+    fn start_analysis(&mut self) {
+        self.start_game(Board::default());
+        self.analysis = true;
+        self.show_position = false;
+    }
+
+    /// Takes back the last move on the analysis board.
+    fn take_back(&mut self) {
+        let mut moves = self.played_moves();
+        if !self.analysis || moves.pop().is_none() {
+            return;
+        }
+
+        //the game can't undo moves, so it is played again without the last one
+        self.game = Game::new_with_board(self.start_position);
+        for mv in moves.iter() {
+            self.game.make_move(*mv);
+        }
+        self.board = self.game.current_position();
+        self.side_to_move = self.board.side_to_move();
+        self.status = BoardStatus::Ongoing;
+        self.replay_boards.pop();
+        self.opening = eco::classify(&self.start_position, &moves);
+        self.piece = (None, None);
+        self.game_changed();
+    }
+
+    /// Starts recording a puzzle at the shown position, or finishes the recording.
+    /// The solution is played out on the board like a game.
+    fn record_puzzle(&mut self) {
+        match self.puzzle_start.take() {
+            None => {
+                //a finished game or replay position continues as a new game from there
+                if self.status != BoardStatus::Ongoing || self.training.is_some() {
+                    self.start_game(self.board);
+                }
+                self.puzzle_start = Some((self.board, self.played_moves().len()));
+                println!("Recording a puzzle, play the solution and press P again.");
+            }
+            Some((board, start)) => {
+                let solution = self.played_moves().iter().skip(start).map(|mv| mv.to_string()).collect::<Vec<String>>();
+                if solution.is_empty() {
+                    println!("No moves were played, the puzzle was not saved.");
+                    return;
+                }
+                self.puzzle_draft = Some(PuzzleDraft {
+                    puzzle: puzzles::Puzzle {
+                        title: format!("Puzzle {}", self.puzzle_db.len() + 1),
+                        rating: puzzles::Difficulty::Medium.rating(),
+                        fen: board.to_string(),
+                        solution,
+                        themes: vec![],
+                    },
+                    difficulty: puzzles::Difficulty::Medium,
+                    themes: String::new(),
+                    editing_themes: false,
+                    typing: false,
+                });
+            }
+        }
+    }
+
+    /// Saves the named puzzle to the puzzles file.
+    fn save_puzzle_draft(&mut self) {
+        if let Some(mut draft) = self.puzzle_draft.take() {
+            if draft.puzzle.title.trim().is_empty() {
+                draft.puzzle.title = "Puzzle".to_string();
+            }
+            draft.puzzle.rating = draft.difficulty.rating();
+            draft.puzzle.themes = draft.themes.split_whitespace().map(|theme| theme.to_string()).collect();
+            match puzzles::append(&self.puzzles_path, &[draft.puzzle.clone()]) {
+                Ok(()) => {
+                    println!("Saved {} to {}", draft.puzzle.title, self.puzzles_path.display());
+                    self.puzzle_db.push(draft.puzzle);
+                }
+                Err(e) => println!("Failed to save puzzle: {}", e),
+            }
+        }
+    }
+
+    /// Saved puzzles with the picked theme.
+    fn filtered_puzzles(&self) -> Vec<&puzzles::Puzzle> {
+        self.puzzle_db.iter()
+            .filter(|puzzle| self.puzzle_theme.as_ref().map_or(true, |theme| puzzle.has_theme(theme)))
+            .collect()
+    }
+
+    /// Picks the next or previous theme to filter puzzles by, all puzzles come before the first theme.
+    fn cycle_puzzle_theme(&mut self, forward: bool) {
+        let themes = puzzles::themes(&self.puzzle_db);
+        let current = self.puzzle_theme.as_ref().and_then(|theme| themes.iter().position(|t| t == theme));
+        let next = match (current, forward) {
+            (None, true) => themes.first().map(|_| 0),
+            (None, false) => themes.len().checked_sub(1),
+            (Some(i), true) => if i + 1 < themes.len() { Some(i + 1) } else { None },
+            (Some(i), false) => i.checked_sub(1),
+        };
+        self.puzzle_theme = next.map(|i| themes[i].clone());
+    }
+
+    /// Starts a random puzzle with the picked theme.
+    fn start_puzzle(&mut self) {
+        let candidates = self.filtered_puzzles();
+        if candidates.is_empty() {
+            println!("There are no puzzles with this theme.");
+            return;
+        }
+        let puzzle = candidates[self.rng.below(candidates.len())].clone();
+
+        let board = match Board::from_str(&puzzle.fen) {
+            Ok(board) => board,
+            Err(e) => {
+                println!("{} has an invalid FEN: {}", puzzle.title, e);
+                return;
+            }
+        };
+        println!("{} ({}), {:?} to move.", puzzle.title, puzzle.rating, board.side_to_move());
+        self.start_game(board);
+        self.set_flipped(board.side_to_move() == Color::Black);
+        self.puzzle_play = Some(PuzzlePlay { puzzle, step: 0, mistakes: 0 });
+    }
+
+    /// Checks a move against the puzzle solution and plays the answer, returns false for a wrong move.
+    /// Any mate on the last move is accepted, like on lichess.
+    fn solve_move(&mut self, mv: ChessMove) -> bool {
+        let play = match &mut self.puzzle_play {
+            Some(play) if self.status == BoardStatus::Ongoing && self.board.legal(mv) => play,
+            _ => return false,
+        };
+
+        let expected = play.puzzle.solution.get(play.step).and_then(|text| remote::parse_uci(text));
+        let last = play.step + 1 >= play.puzzle.solution.len();
+        let mates = self.board.make_move_new(mv).status() == BoardStatus::Checkmate;
+        if expected != Some(mv) && !(last && mates) {
+            play.mistakes += 1;
+            self.show_toast("Not the move, try again");
+            return false;
+        }
+
+        let reply = play.puzzle.solution.get(play.step + 1).and_then(|text| remote::parse_uci(text));
+        play.step += if reply.is_some() { 2 } else { 1 };
+        let solved = last || play.step >= play.puzzle.solution.len();
+        let mistakes = play.mistakes;
+
+        self.game.make_move(mv);
+        if let (Some(reply), false) = (reply, last) {
+            self.game.make_move(reply);
+        }
+        self.board = self.game.current_position();
+        self.side_to_move = self.board.side_to_move();
+        self.replay_boards.push(self.board);
+
+        if solved {
+            let result = format!("Solved with {} mistakes", mistakes);
+            println!("{}", result);
+            self.status = BoardStatus::Checkmate;
+            self.result = Some(result);
+        }
+        true
+    }
+
+    /// Starts guess-the-move training on the last saved game, guessing the side at the bottom of the board.
+    fn start_training(&mut self) {
+        let boards = match self.saved_replay.last() {
+            Some(replay) => replay.boards(),
+            None => return,
+        };
+        let side = if self.flipped { Color::Black } else { Color::White };
+        let training = training::GuessTraining::new(boards, side);
+        if training.finished() {
+            println!("{:?} has no moves to guess in the last game.", side);
+            return;
+        }
+
+        println!("Guess the moves of {:?}.", side);
+        self.show_training_position(training.position());
+        self.training = Some(training);
+        self.result = None;
+        self.overlay.clear();
+    }
+
+    /// Sets up the board at a training position so the guess can be dragged like a move.
+    fn show_training_position(&mut self, board: Board) {
+        self.board = board;
+        self.game = Game::new_with_board(board);
+        self.side_to_move = board.side_to_move();
+        self.status = BoardStatus::Ongoing;
+        self.piece = (None, None);
+        self.replay_turn = 999;
+    }
+
+    /// Scores a guessed move and shows the game move, returns false if the move is not legal.
+    fn guess_move(&mut self, mv: ChessMove) -> bool {
+        let training = match &mut self.training {
+            Some(training) if self.board.legal(mv) => training,
+            _ => return false,
+        };
+        let guess = match training.guess(mv) {
+            Some(guess) => guess,
+            None => return false,
+        };
+
+        //a wrong guess gets an arrow for the game move
+        self.overlay.clear();
+        let feedback = if guess.matched {
+            format!("Correct, {} was played", guess.actual)
+        } else {
+            format!("{} was played, your move lost {} cp", guess.actual, guess.loss)
+        };
+        if !guess.matched {
+            self.overlay.arrow(guess.actual.get_source(), guess.actual.get_dest(), graphics::Color::new(0.2, 0.8, 0.2, 0.7));
+        }
+
+        if training.finished() {
+            let summary = training.summary();
+            println!("{}", summary);
+            self.status = BoardStatus::Checkmate;
+            self.result = Some(summary);
+        } else {
+            let position = training.position();
+            self.show_training_position(position);
+        }
+        self.show_toast(&feedback);
+        true
+    }
+
+    /// A position from a random game, 8 to 40 moves in, where there is at least one check or capture.
+    fn random_position(&mut self) -> Board {
+        loop {
+            let mut board = Board::default();
+            let length = 8 + self.rng.below(33);
+            for _ in 0..length {
+                let moves = MoveGen::new_legal(&board).collect::<Vec<ChessMove>>();
+                if moves.is_empty() {
+                    break;
+                }
+                board = board.make_move_new(moves[self.rng.below(moves.len())]);
+            }
+            if board.status() == BoardStatus::Ongoing && !vision::checks_and_captures(&board).is_empty() {
+                return board;
+            }
+        }
+    }
+
+    /// Starts a vision drill on a random position: every check and capture has to be played before the time is up.
+    fn start_vision(&mut self) {
+        let drill = vision::VisionDrill::new(self.random_position());
+        println!("Find every check and capture for {:?} in {:.0} seconds.", drill.board().side_to_move(), vision::TIME_LIMIT);
+        self.start_game(drill.board());
+        self.vision = Some(drill);
+        self.overlay.clear();
+    }
+
+    /// Checks a move of the vision drill, found moves get a green arrow and the position stays.
+    fn vision_move(&mut self, mv: ChessMove) -> bool {
+        let drill = match &mut self.vision {
+            Some(drill) if self.board.legal(mv) => drill,
+            _ => return false,
+        };
+        let found = match drill.attempt(mv) {
+            Some(vision::Attempt::Found) => {
+                self.overlay.arrow(mv.get_source(), mv.get_dest(), graphics::Color::new(0.2, 0.8, 0.2, 0.7));
+                true
+            }
+            Some(vision::Attempt::AlreadyFound) => {
+                self.show_toast("Already found");
+                false
+            }
+            Some(vision::Attempt::Wrong) => {
+                self.show_toast("Not a check or capture");
+                false
+            }
+            None => false,
+        };
+        self.finish_vision();
+        found
+    }
+
+    /// Ends the vision drill once it is finished, the missed moves get red arrows.
+    fn finish_vision(&mut self) {
+        let drill = match &self.vision {
+            Some(drill) if drill.finished() && self.status == BoardStatus::Ongoing => drill,
+            _ => return,
+        };
+        for mv in drill.missed() {
+            self.overlay.arrow(mv.get_source(), mv.get_dest(), graphics::Color::new(0.85, 0.2, 0.2, 0.7));
+        }
+        let summary = drill.summary();
+        println!("{}", summary);
+        self.status = BoardStatus::Checkmate;
+        self.promotion_choice = None;
+        self.result = Some(summary);
+    }
+
+    /// Carries out the commands that other programs have queued.
+    fn run_commands(&mut self) {
+        let commands = std::mem::take(&mut *self.inbox.lock().expect("Inbox lock poisoned"));
+        for command in commands {
+            match command {
+                remote::Command::NewGame => self.new_game(),
+                remote::Command::Move(text) => {
+                    let played = remote::parse_uci(&text).map_or(false, |mv| self.play_move(mv));
+                    if !played {
+                        println!("Remote move {} is not legal here.", text);
+                    } else if !self.focused {
+                        self.move_notification = Some(format!("Move {} was played. {}", text, self.status_line()));
+                    }
+                }
+                remote::Command::Fen(fen) => {
+                    //lists everything that is wrong with the position instead of only refusing it
+                    let mut problems = validate::problems(&fen);
+                    match Board::from_str(&fen) {
+                        Ok(board) if problems.is_empty() => {
+                            //the analysis board stays open with the new position
+                            let analysis = self.analysis;
+                            self.position_problems = None;
+                            self.start_game(board);
+                            self.analysis = analysis;
+                        }
+                        result => {
+                            if let (Err(e), true) = (result, problems.is_empty()) {
+                                problems.push(e.to_string());
+                            }
+                            self.position_problems = Some((fen, problems));
+                        }
+                    }
+                }
+                remote::Command::Flip => self.set_flipped(!self.flipped),
+                remote::Command::Open(path) => self.open_file(&path),
+                remote::Command::SavePgn(path) => match std::fs::write(&path, self.game_pgn()) {
+                    Ok(()) => println!("Saved the game to {}", path),
+                    Err(e) => println!("Failed to save PGN {}: {}", path, e),
+                },
+                remote::Command::SaveMarkdown(path, evals) => match std::fs::write(&path, self.game_markdown(evals)) {
+                    Ok(()) => println!("Saved the game report to {}", path),
+                    Err(e) => println!("Failed to save the report {}: {}", path, e),
+                },
+                remote::Command::SaveScoresheet(path) => {
+                    let (tags, result) = self.game_tags();
+                    match std::fs::write(&path, scoresheet::write(&tags, &self.start_position, &self.played_moves(), result)) {
+                        Ok(()) => println!("Saved the scoresheet to {}", path),
+                        Err(e) => println!("Failed to save the scoresheet {}: {}", path, e),
+                    }
+                }
+                remote::Command::ImportPuzzles(path, filter) => {
+                    if self.puzzle_import.is_some() {
+                        println!("A puzzle import is already running.");
+                        continue;
+                    }
+                    let progress = puzzles::SharedProgress::default();
+                    puzzles::import_lichess(path.into(), self.puzzles_path.clone(), filter, progress.clone());
+                    self.puzzle_import = Some(progress);
+                }
+                remote::Command::ImportGames(path) => self.import_games(&path),
+            }
+        }
+    }
+
+    /// Opens a .fen file as a new game, a .pgn file as the game the Replays button shows or a .lesson file.
+    fn open_file(&mut self, path: &str) {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("Failed to open {}: {}", path, e);
+                return;
+            }
+        };
+
+        if path.to_lowercase().ends_with(".lesson") {
+            self.open_lesson(path, &text);
+            return;
+        }
+
+        if path.to_lowercase().ends_with(".fen") {
+            let fen = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim().to_string();
+            self.inbox.lock().expect("Inbox lock poisoned").push(remote::Command::Fen(fen));
+            return;
+        }
+
+        //a file with more than one game is a database, its games are imported in the background
+        let mut seen_moves = false;
+        let several = text.lines().map(|line| line.trim()).any(|line| {
+            if line.starts_with('[') {
+                seen_moves
+            } else {
+                seen_moves |= !line.is_empty();
+                false
+            }
+        });
+        if several {
+            self.import_games(path);
+            return;
+        }
+
+        match pgn::read(&text) {
+            Ok((start, moves)) => {
+                self.saved_replay.insert(0, replay::Replay::new(start, &moves).with_info(replay::GameInfo::from_pgn(&text)));
+                println!("Opened {} with {} moves, press Replays to go through it.", path, moves.len());
+            }
+            Err(e) => println!("Failed to read {}: {}", path, e),
+        }
+    }
+
+    /// The indices of the saved replays matching the replay search, all of them when nothing is searched.
+    fn filtered_replays(&self) -> Vec<usize> {
+        (0..self.saved_replay.len())
+            .filter(|&i| self.replay_search.as_ref().map_or(true, |search| self.saved_replay[i].matches(search)))
+            .collect()
+    }
+
+    /// Starts importing all games of a PGN file as replays.
+    fn import_games(&mut self, path: &str) {
+        if self.game_import.is_some() {
+            println!("A game import is already running.");
+            return;
+        }
+        let progress = import::SharedImport::default();
+        import::import_pgn(path.into(), self.imported_games.clone(), progress.clone());
+        self.game_import = Some(progress);
+    }
+
+    /// Starts kiosk mode in fullscreen, playing the games of a PGN file in a loop.
+    fn start_kiosk(&mut self, ctx: &mut Context, path: &str) {
+        self.setup = None;
+        self.kiosk = Some(Kiosk { game: 0, ply: None, wait: 0.0 });
+        self.import_games(path);
+        if let Err(e) = graphics::set_fullscreen(ctx, conf::FullscreenType::Desktop) {
+            println!("Failed to go fullscreen: {}", e);
+        }
+    }
+
+    /// Plays the next kiosk move when it is time, or sets up the next game after a finished one.
+    fn kiosk_step(&mut self, seconds: f32) {
+        let mut kiosk = match self.kiosk {
+            Some(kiosk) => kiosk,
+            None => return,
+        };
+        kiosk.wait -= seconds;
+        if kiosk.wait > 0.0 || self.saved_replay.is_empty() {
+            self.kiosk = Some(kiosk);
+            return;
+        }
+
+        let replay = &self.saved_replay[kiosk.game % self.saved_replay.len()];
+        let (start, moves) = (replay.board_at(0).unwrap_or_default(), replay.moves());
+        match kiosk.ply {
+            None => {
+                //on the analysis board nothing is recorded and a mate doesn't end anything
+                self.start_game(start);
+                self.analysis = true;
+                kiosk.ply = Some(0);
+                kiosk.wait = KIOSK_MOVE_TIME;
+            }
+            Some(ply) if ply < moves.len() => {
+                self.play_move(moves[ply]);
+                kiosk.ply = Some(ply + 1);
+                kiosk.wait = if ply + 1 == moves.len() { KIOSK_GAME_PAUSE } else { KIOSK_MOVE_TIME };
+            }
+            Some(_) => {
+                kiosk.game = (kiosk.game + 1) % self.saved_replay.len();
+                kiosk.ply = None;
+            }
+        }
+        self.kiosk = Some(kiosk);
+    }
+
+    /// True when only the board is drawn, in stream, kiosk and presentation mode.
+    fn board_only(&self) -> bool {
+        self.stream_mode || self.kiosk.is_some() || self.presentation.is_some()
+    }
+
+    /// Starts the API server on an address, e.g. 127.0.0.1:8080.
+    fn serve(&mut self, address: &str) {
+        let shared = remote::SharedState::default();
+        match remote::serve(address, shared.clone(), self.inbox.clone()) {
+            Ok(()) => {
+                self.shared_state = Some(shared);
+                self.game_changed();
+            }
+            Err(e) => println!("Failed to serve on {}: {}", address, e),
+        }
+    }
+
+    /// Moves played in the running game, or the last one if it has ended.
+    fn played_moves(&self) -> Vec<ChessMove> {
+        self.game.actions().iter()
+            .filter_map(|action| match action {
+                chess::Action::MakeMove(mv) => Some(*mv),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Shares the running game with the API server and the live PGN file after it changed.
+    fn game_changed(&self) {
+        if let Some(shared) = &self.shared_state {
+            let mut board = self.start_position;
+            let mut moves = vec![];
+            for mv in self.played_moves() {
+                moves.push(pgn::san(&board, mv));
+                board = board.make_move_new(mv);
+            }
+
+            let mut snapshot = shared.lock().expect("Shared state lock poisoned");
+            snapshot.fen = self.board.to_string();
+            snapshot.moves = moves;
+            snapshot.status = self.status_line();
+        }
+
+        //rewrites the live PGN file, if one is set in the settings
+        if let Some(path) = &self.settings.live_pgn {
+            if let Err(e) = std::fs::write(path, self.game_pgn()) {
+                println!("Failed to write live PGN {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Short status of the game, e.g. "White to move" or the result when it has ended.
+    fn status_line(&self) -> String {
+        match (&self.result, self.status) {
+            (Some(result), BoardStatus::Checkmate) => result.clone(),
+            (None, BoardStatus::Checkmate) => "Not started".to_string(),
+            _ => format!("{:?} to move", self.side_to_move),
+        }
+    }
+
+    /// The running game, or the last one if it has ended, as PGN.
+    fn game_pgn(&self) -> String {
+        let (tags, result) = self.game_tags();
+        pgn::write(&tags, &self.start_position, &self.played_moves(), result)
+    }
+
+    /// The game as a Markdown report, see pgn::markdown.
+    fn game_markdown(&self, evals: bool) -> String {
+        let (tags, result) = self.game_tags();
+        pgn::markdown(&tags, &self.start_position, &self.played_moves(), result, evals)
+    }
+
+    /// The tag pairs and the result of the running game.
+    fn game_tags(&self) -> (Vec<(&'static str, String)>, &'static str) {
+        let result = match self.game.result() {
+            Some(chess::GameResult::WhiteCheckmates) | Some(chess::GameResult::BlackResigns) => "1-0",
+            Some(chess::GameResult::BlackCheckmates) | Some(chess::GameResult::WhiteResigns) => "0-1",
+            Some(_) => "1/2-1/2",
+            None => "*",
+        };
+
+        //the round counts the games of the current match, a finished game is already in the score
+        let white = &self.players[Color::White.to_index()].name;
+        let black = &self.players[Color::Black.to_index()].name;
+        let played = match &self.match_score {
+            Some(score) if score.is_between(white, black) => (score.halves[0] + score.halves[1]) / 2,
+            _ => 0,
+        };
+        let round = if result == "*" { played + 1 } else { played.max(1) };
+
+        let tags = vec![
+            ("Event", "Casual game".to_string()),
+            ("Site", "Schack".to_string()),
+            ("Date", pgn::date_today()),
+            ("Round", round.to_string()),
+            ("White", white.clone()),
+            ("Black", black.clone()),
+        ];
+        (tags, result)
+    }
+
+    /// Plays a move in the running game, returns false if it is not legal.
+    fn play_move(&mut self, mv: ChessMove) -> bool {
+        //during training the move is a guess and in a puzzle it is checked against the solution
+        if self.training.is_some() {
+            return self.guess_move(mv);
+        }
+        if self.puzzle_play.is_some() {
+            return self.solve_move(mv);
+        }
+        if self.vision.is_some() {
+            return self.vision_move(mv);
+        }
+
+        if self.status != BoardStatus::Ongoing || !self.game.make_move(mv) {
+            return false;
+        }
+        let claimed = std::mem::take(&mut self.draw_claim);
+        if let Some(dropped) = self.drop_time.take() {
+            self.move_latency = Some(dropped.elapsed());
+        }
+        self.touched = None;
+
+        //Updates board and status
+        self.board = self.game.current_position();
+        self.status = self.board.status();
+        if self.start_position == Board::default() {
+            self.opening = eco::lookup(&self.board).or(self.opening);
+        }
+
+        //the analysis board shows the mate or stalemate but doesn't end anything, so moves can be taken back
+        if self.analysis {
+            self.replay_boards.push(self.board);
+            self.side_to_move = self.board.side_to_move();
+            self.game_changed();
+            return true;
+        }
+
+        //Plays the game over effect
+        if self.effects {
+            match self.status {
+                BoardStatus::Checkmate => self.celebration.fireworks(),
+                BoardStatus::Stalemate => self.celebration.fade(),
+                BoardStatus::Ongoing => {}
+            }
+        }
+
+        //Saves the the board for replay after game has ended
+        self.replay_boards.push(self.board);
+        self.journal_move(mv);
+
+        println!("{:?} move: {}\nboard: {}\nStatus: {:?}", self.side_to_move, mv, self.board, self.status);
+
+        if self.status == BoardStatus::Checkmate {
+            //Saves the moves to the replay vector and shows the winner.
+            self.end_game(Some(self.side_to_move), format!("{:?} won by checkmate!", self.side_to_move));
+        } else if self.status == BoardStatus::Stalemate {
+            self.end_game(None, "Draw by stalemate.".to_string());
+        } else if claimed && self.game.can_declare_draw() {
+            self.game.declare_draw();
+            self.end_game(None, format!("Draw claimed by {}.", self.claim_rule()));
+        } else {
+            if claimed {
+                //an incorrect claim doesn't take the move back, like over the board
+                let (repetitions, quiet_moves) = self.claim_counts();
+                println!(
+                    "Claim rejected: the position has occurred {} time(s) and {} move(s) were made without a capture or pawn move. The move stands.",
+                    repetitions, quiet_moves,
+                );
+                self.show_toast("Draw claim rejected");
+            }
+            self.side_to_move = !self.side_to_move;
+            self.game_changed();
+        }
+
+        //Turns the board towards the next player
+        if self.auto_flip && self.status == BoardStatus::Ongoing {
+            self.set_flipped(self.side_to_move == Color::Black);
+        }
+
+        true
+    }
+
+    /// Writes a played move to the journal, which is started over at the first move of a game.
+    fn journal_move(&self, mv: ChessMove) {
+        let started = if self.played_moves().len() == 1 {
+            journal::start(&self.journal_path, &self.start_position)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = started.and_then(|_| journal::add_move(&self.journal_path, mv)) {
+            println!("Failed to write the journal: {}", e);
+        }
+    }
+
+    /// Plays an unfinished game from the journal again, after a crash or when the GUI was closed during a game.
+    fn recover_journal(&mut self) {
+        let (start, moves) = match journal::unfinished(&self.journal_path) {
+            Some(game) => game,
+            None => return,
+        };
+        self.start_game(start);
+        let played = moves.into_iter().take_while(|mv| self.play_move(*mv)).count();
+        println!("Recovered an unfinished game with {} moves from {}.", played, self.journal_path.display());
+        self.show_toast("Recovered the unfinished game");
+    }
+
+    /// Claims a draw for the side to move. Under the FIDE rules a draw can be claimed when the position
+    /// has occurred three times or after fifty moves without a capture or pawn move, either in the
+    /// position on the board or with the move the player is about to make.
+    fn claim_draw(&mut self) {
+        if self.game.can_declare_draw() {
+            self.game.declare_draw();
+            self.end_game(None, format!("Draw claimed by {}.", self.claim_rule()));
+            return;
+        }
+        self.draw_claim = true;
+        println!("{:?} claims a draw, play the move that repeats the position or makes the fiftieth move.", self.side_to_move);
+        self.show_toast("Play the move you claim the draw with");
+    }
+
+    /// How often the position on the board has occurred, and the moves in a row without a capture or pawn move.
+    fn claim_counts(&self) -> (usize, usize) {
+        let repetitions = self.replay_boards.iter().filter(|board| **board == self.board).count();
+        let mut quiet_plies = 0;
+        let mut board = self.start_position;
+        for mv in self.played_moves() {
+            let reversible = board.piece_on(mv.get_source()) != Some(Piece::Pawn) && board.piece_on(mv.get_dest()).is_none();
+            quiet_plies = if reversible { quiet_plies + 1 } else { 0 };
+            board = board.make_move_new(mv);
+        }
+        (repetitions, quiet_plies / 2)
+    }
+
+    /// The rule a correct draw claim was made under.
+    fn claim_rule(&self) -> &'static str {
+        match self.claim_counts() {
+            (repetitions, _) if repetitions >= 3 => "threefold repetition",
+            _ => "the fifty-move rule",
+        }
+    }
+
+    /// Starts the next game of the series with the players' colors swapped.
+    fn rematch(&mut self) {
+        self.players.swap(0, 1);
+        self.new_game();
+    }
+
+    /// Points of a player in the match between the current players, None if they have no match yet.
+    fn match_points(&self, color: Color) -> Option<String> {
+        let score = self.match_score.as_ref()?;
+        if !score.is_between(&self.players[0].name, &self.players[1].name) {
+            return None;
+        }
+        score.points_of(&self.players[color.to_index()].name)
+    }
+
+    /// Resizes the window to a new UI scale, everything inside is scaled along with it.
+    fn set_ui_scale(&mut self, ctx: &mut Context, scale: f32) -> GameResult {
+        //steps of 5% between 75% and 200%
+        self.settings.ui_scale = ((scale * 20.0).round() / 20.0).max(0.75).min(2.0);
+        graphics::set_drawable_size(ctx, SCREEN_SIZE.0 * self.settings.ui_scale, SCREEN_SIZE.1 * self.settings.ui_scale)?;
+        graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1))
+    }
+
+    /// Sets the UI scale from where the mouse is on the slider, x in unscaled screen coordinates.
+    fn drag_ui_scale(&mut self, ctx: &mut Context, x: f32) {
+        let menu_x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+        let scale = 0.75 + 1.25 * ((x - menu_x - 20.0) / 300.0).max(0.0).min(1.0);
+        if let Err(e) = self.set_ui_scale(ctx, scale) {
+            println!("Failed to change UI scale: {}", e);
+        }
+    }
+
+    /// Mouse position in unscaled screen coordinates.
+    fn mouse_position(&self, ctx: &mut Context) -> ggez::mint::Point2<f32> {
+        let pos = input::mouse::position(ctx);
+        window_to_screen(ctx, pos.x, pos.y)
+    }
+
+    /// The clickable regions shown right now, in the order they are drawn.
+    fn hit_regions(&self, ctx: &Context) -> hit::HitRegions {
+        let menu_x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+        let board_size = 8.0 * GRID_CELL_SIZE.0 as f32;
+        //the menu is left out when only the board is drawn
+        let menu = self.status == BoardStatus::Checkmate && !self.board_only();
+        let mut regions = hit::HitRegions::default();
+
+        if menu {
+            regions.add(HitRegion::StartButton, graphics::Rect::new(menu_x, 100.0, 340.0, 60.0));
+            regions.add(HitRegion::ReplaysButton, graphics::Rect::new(menu_x, 160.0, 340.0, 60.0));
+            regions.add(HitRegion::AnalysisButton, graphics::Rect::new(menu_x, 280.0, 340.0, 60.0));
+
+            //the replay list stays open while the mouse is over the replays button or the list, or while it is searched
+            let rows = self.filtered_replays().len().min(REPLAY_LIST_ROWS);
+            let list = graphics::Rect::new(menu_x, 220.0, 340.0, 30.0 * rows as f32);
+            let mouse = input::mouse::position(ctx);
+            let pos = window_to_screen(ctx, mouse.x, mouse.y);
+            if self.replay_search.is_some() || regions.hovered(HitRegion::ReplaysButton, pos.x, pos.y) || (rows > 0 && list.contains(pos)) {
+                for i in 0..rows {
+                    regions.add(HitRegion::ReplayEntry(i), graphics::Rect::new(menu_x, 220.0 + 30.0 * i as f32, 340.0, 30.0));
+                }
+            }
+        }
+
+        regions.add(HitRegion::Board, graphics::Rect::new(20.0, 20.0, board_size, board_size));
+
+        if menu {
+            regions.add(HitRegion::TopNamePlate, graphics::Rect::new(menu_x, 20.0, 340.0, 60.0));
+            regions.add(HitRegion::BottomNamePlate, graphics::Rect::new(menu_x, 20.0 + board_size - 60.0, 340.0, 60.0));
+        }
+        if !self.board_only() {
+            regions.add(HitRegion::ScaleSlider, graphics::Rect::new(menu_x + 10.0, SCALE_SLIDER_Y - 15.0, 320.0, 30.0));
+        }
+        if menu && self.result.is_some() && self.training.is_none() && self.puzzle_play.is_none() && self.vision.is_none() {
+            regions.add(HitRegion::RematchButton, graphics::Rect::new(menu_x, 570.0, 340.0, 60.0));
+        }
+
+        if let Some((_, to)) = self.promotion_choice {
+            for (i, (_, rect)) in promotion_rects(to, self.view_flipped()).into_iter().enumerate() {
+                regions.add(HitRegion::PromotionPiece(i), rect);
+            }
+        }
+        regions
+    }
+
+    /// Draws the UI scale slider in the menu.
+    fn draw_scale_slider(&self, ctx: &mut Context) -> GameResult {
+        let x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+
+        let label = styled_text(&format!("UI scale: {:.0}%", self.settings.ui_scale * 100.0), self.font, TEXT_NORMAL);
+        graphics::draw(ctx, &label, graphics::DrawParam::default().dest([x + 20.0, 470.0]))?;
+
+        let track = graphics::Mesh::new_line(
+            ctx,
+            &[[x + 20.0, SCALE_SLIDER_Y], [x + 320.0, SCALE_SLIDER_Y]],
+            4.0,
+            graphics::Color::new(0.7, 0.7, 0.7, 1.0),
+        )?;
+        graphics::draw(ctx, &track, graphics::DrawParam::default())?;
+
+        let knob = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            [x + 20.0 + 300.0 * (self.settings.ui_scale - 0.75) / 1.25, SCALE_SLIDER_Y],
+            9.0,
+            0.5,
+            graphics::Color::new(1.0, 1.0, 1.0, 1.0),
+        )?;
+        graphics::draw(ctx, &knob, graphics::DrawParam::default())?;
+
+        Ok(())
+    }
+
+    /// Draws the rematch button after a game.
+    fn draw_rematch_button(&self, ctx: &mut Context, regions: &hit::HitRegions) -> GameResult {
+        if let Some(rect) = regions.rect(HitRegion::RematchButton) {
+            let pos = self.mouse_position(ctx);
+            let rematch_button = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                button_color(regions.hovered(HitRegion::RematchButton, pos.x, pos.y)),
+            )?;
+            graphics::draw(ctx, &rematch_button, graphics::DrawParam::default())?;
+
+            let rematch_text = styled_text("Rematch", self.font, TEXT_LARGE);
+            let rematch_width = rematch_text.width(ctx);
+            graphics::draw(
+                ctx,
+                &rematch_text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([rect.x + 170.0 - rematch_width / 2.0, rect.y + 15.0]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a finished game to the stats of the profiles that played it and saves them.
+    fn record_result(&mut self, winner: Option<Color>) {
+        for color in chess::ALL_COLORS.iter() {
+            if let Some(index) = self.players[color.to_index()].profile {
+                let profile = &mut self.profiles[index];
+                match winner {
+                    Some(winner) if winner == *color => profile.wins += 1,
+                    Some(_) => profile.losses += 1,
+                    None => profile.draws += 1,
+                }
+            }
+        }
+
+        if let Err(e) = profiles::save(&self.profiles_path, &self.profiles) {
+            println!("Failed to save profiles: {}", e);
+        }
+    }
+
+    /// Draws the last move in large text where the menu usually is, for stream mode.
+    fn draw_last_move(&self, ctx: &mut Context) -> GameResult {
+        let moves = self.played_moves();
+        let (last, before) = match (moves.last(), self.replay_boards.len()) {
+            (Some(last), len) if len >= 2 => (*last, self.replay_boards[len - 2]),
+            _ => return Ok(()),
+        };
+
+        //move numbers are written "12." for White and "12..." for Black
+        let ply = moves.len() + if self.start_position.side_to_move() == Color::Black { 1 } else { 0 };
+        let number = (ply + 1) / 2;
+        let dots = if ply % 2 == 1 { "." } else { "..." };
+        let move_text = styled_text(&format!("{}{} {}", number, dots, pgn::san(&before, last)), self.font, TEXT_HUGE);
+        let label = styled_text("Last move", self.font, TEXT_MEDIUM);
+        let move_width = move_text.width(ctx);
+        let label_width = label.width(ctx);
+
+        let center = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 170.0;
+        graphics::draw(ctx, &label, graphics::DrawParam::default().dest([center - label_width / 2.0, 300.0]))?;
+        graphics::draw(ctx, &move_text, graphics::DrawParam::default().dest([center - move_width / 2.0, 340.0]))
+    }
+
+    /// Signs in the next profile, after the last profile the shared settings are used again.
+    /// Every profile has its own settings file and replays, so students sharing a machine don't change each other's.
+    fn next_user(&mut self, ctx: &mut Context) {
+        let next = match self.user {
+            None if !self.profiles.is_empty() => Some(0),
+            Some(i) if i + 1 < self.profiles.len() => Some(i + 1),
+            _ => None,
+        };
+
+        self.save_settings();
+        let replays = std::mem::take(&mut self.saved_replay);
+        self.user_replays.insert(self.user, replays);
+        self.saved_replay = self.user_replays.remove(&next).unwrap_or_default();
+        self.replay_view = None;
+        self.replay_search = None;
+        self.user = next;
+
+        self.settings_path = match next {
+            Some(i) => self.data_dir.join("users").join(user_file_name(&self.profiles[i].name)),
+            None => self.data_dir.join("settings.txt"),
+        };
+        self.settings = settings::Settings::load(&self.settings_path);
+        self.font = load_font(ctx, &self.settings);
+        self.stream_mode = self.settings.stream_mode;
+        if let Err(e) = self.set_ui_scale(ctx, self.settings.ui_scale) {
+            println!("Failed to change UI scale: {}", e);
+        }
+        let texture = self.settings.texture.as_ref().and_then(|name| BOARD_TEXTURES.iter().position(|(texture, _, _)| *texture == name.as_str()));
+        self.theme.set_texture(ctx, texture);
+
+        let message = match next {
+            Some(i) => format!("Signed in as {}", self.profiles[i].name),
+            None => "Shared settings".to_string(),
+        };
+        self.show_toast(&message);
+    }
+
+    /// Saves the settings after they were changed in the game.
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(&self.settings_path) {
+            println!("Failed to save settings: {}", e);
+        }
+    }
+
+    /// Turns vsync on or off. The window is created with the ggez config file,
+    /// so the change is written there and takes effect the next time the game starts.
+    fn toggle_vsync(&mut self, ctx: &mut Context) {
+        self.settings.vsync = !self.settings.vsync;
+        self.save_settings();
+        //portable mode reads vsync from its own settings at startup, the ggez config file isn't written
+        let written = if portable_dir().is_some() { Ok(()) } else { ggez::filesystem::write_config(ctx, &window_conf(self.settings.vsync)) };
+        match written {
+            Ok(()) => println!("Vsync: {}, restart to apply", if self.settings.vsync { "On" } else { "Off" }),
+            Err(e) => println!("Failed to save window config: {}", e),
+        }
+    }
+
+    /// Cycles the frame limit between 30, 60, 120 and unlimited.
+    fn next_fps_cap(&mut self) {
+        self.settings.fps_cap = match self.settings.fps_cap {
+            Some(cap) if cap < 60 => Some(60),
+            Some(cap) if cap < 120 => Some(120),
+            Some(_) => None,
+            None => Some(30),
+        };
+        self.save_settings();
+        match self.settings.fps_cap {
+            Some(cap) => println!("FPS cap: {}", cap),
+            None => println!("FPS cap: Unlimited"),
+        }
+    }
+
+    /// Draws the diagnostics in the top left corner: frame rate, frame time percentiles, board draw calls,
+    /// the time from dropping a piece until its move was played, how much memory the replays take and the opening.
+    fn draw_stats(&self, ctx: &mut Context) -> GameResult {
+        let mut sorted = self.frame_times.iter().copied().collect::<Vec<f32>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: usize| sorted.get((sorted.len() * p / 100).min(sorted.len().saturating_sub(1))).copied().unwrap_or(0.0);
+
+        //saved replays are packed, the running game is kept as boards
+        let boards = self.saved_replay.iter().map(|replay| replay.len()).sum::<usize>() + self.replay_boards.len();
+        let unpacked = self.replay_boards.len() * std::mem::size_of::<Board>();
+        let replay_kb = (self.saved_replay.iter().map(|replay| replay.memory()).sum::<usize>() + unpacked) as f32 / 1024.0;
+        let latency = self.move_latency.map_or("-".to_string(), |latency| format!("{:.1} ms", latency.as_secs_f32() * 1000.0));
+
+        let stats = styled_text(
+            &format!(
+                "FPS: {:.0}\nFrame: {:.1} ms (p50 {:.1}, p95 {:.1}, p99 {:.1})\nBoard draw calls: {}\nDrop to move: {}\nReplays: {} positions, {:.0} KB\nSeed: {}\nOpening: {}",
+                timer::fps(ctx),
+                timer::average_delta(ctx).as_secs_f32() * 1000.0,
+                percentile(50),
+                percentile(95),
+                percentile(99),
+                self.board_draws,
+                latency,
+                boards,
+                replay_kb,
+                self.seed,
+                self.opening.map_or("-".to_string(), |opening| opening.label()),
+            ),
+            self.font,
+            TEXT_SMALL,
+        );
+        let dimensions = stats.dimensions(ctx);
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(20.0, 20.0, dimensions.w + 12.0, dimensions.h + 12.0),
+            BADGE_COLOR,
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx, &stats, graphics::DrawParam::default().dest([26.0, 26.0]))
+    }
+
+    /// True when the key bindings of a context work right now.
+    fn key_context_active(&self, context: KeyContext) -> bool {
+        match context {
+            KeyContext::Everywhere => true,
+            KeyContext::Game => {
+                self.status != BoardStatus::Checkmate && self.training.is_none() && self.puzzle_play.is_none() && self.vision.is_none() && !self.analysis
+            }
+            //the arrow keys move the square cursor in presentation mode and step through lessons instead of picking puzzle themes
+            KeyContext::Menu => self.status == BoardStatus::Checkmate && self.presentation.is_none() && self.lesson_view.is_none(),
+            KeyContext::Replay => self.status == BoardStatus::Checkmate,
+            KeyContext::Analysis => self.analysis,
+            KeyContext::Presentation => self.presentation.is_some(),
+            KeyContext::Lesson => self.lesson_view.is_some(),
+        }
+    }
+
+    /// Does what a key is bound to.
+    fn run_action(&mut self, ctx: &mut Context, action: Action) {
+        match action {
+            //resigning and offering a draw ask for confirmation first
+            Action::Resign => self.confirm = Some(Confirm::Resign),
+            Action::OfferDraw => self.confirm = Some(Confirm::OfferDraw),
+            Action::ClaimDraw => self.claim_draw(),
+            Action::ReplayForward => {
+                if self.replay_view.as_ref().map_or(false, |replay| self.replay_turn + 1 < replay.len()) { self.replay_turn += 1; }
+            }
+            Action::ReplayBack => {
+                if self.replay_turn >= 1 { self.replay_turn -= 1; }
+            }
+            Action::NextTexture => {
+                self.theme.next_texture(ctx);
+                self.settings.texture = self.theme.texture_name();
+                self.save_settings();
+            }
+            Action::LessTint => self.theme.adjust_tint(-0.1),
+            Action::MoreTint => self.theme.adjust_tint(0.1),
+            Action::ToggleArrows => {
+                self.attack_arrows = !self.attack_arrows;
+                println!("Attack arrows: {}", if self.attack_arrows { "On" } else { "Off" });
+            }
+            Action::StartVision => self.start_vision(),
+            Action::ToggleMobility => {
+                self.mobility_hint = !self.mobility_hint;
+                println!("Mobility hint: {}", if self.mobility_hint { "On" } else { "Off" });
+            }
+            Action::TogglePositionalHints => {
+                self.positional_hints = !self.positional_hints;
+                println!("Positional hints: {}", if self.positional_hints { "On" } else { "Off" });
+            }
+            Action::ToggleAutoFlip => {
+                self.auto_flip = !self.auto_flip;
+                if self.auto_flip { self.set_flipped(self.side_to_move == Color::Black); }
+                println!("Auto-flip: {}", if self.auto_flip { "On" } else { "Off" });
+            }
+            Action::SlowerFlip | Action::FasterFlip => {
+                let change = if action == Action::SlowerFlip { -0.1 } else { 0.1 };
+                self.flip_duration = (self.flip_duration + change).max(0.0).min(2.0);
+                println!("Flip duration: {:.1}s", self.flip_duration);
+            }
+            Action::TakeBack => self.take_back(),
+            Action::ShowPosition => self.show_position = !self.show_position,
+            Action::CloseAnalysis => {
+                self.analysis = false;
+                self.status = BoardStatus::Checkmate;
+                self.promotion_choice = None;
+            }
+            Action::MarkPosition => {
+                self.marked_position = Some(self.board);
+                println!("Marked the position for comparing, C compares.");
+            }
+            Action::Compare => {
+                if self.marked_position.is_some() { self.comparing = !self.comparing; }
+            }
+            Action::StartPuzzle => self.start_puzzle(),
+            Action::PreviousTheme => self.cycle_puzzle_theme(false),
+            Action::NextTheme => self.cycle_puzzle_theme(true),
+            Action::StartTraining => self.start_training(),
+            Action::SearchReplays => self.replay_search = Some(String::new()),
+            Action::NextUser => self.next_user(ctx),
+            //the second time saves the recorded puzzle
+            Action::RecordPuzzle => self.record_puzzle(),
+            Action::ToggleStats => self.show_stats = !self.show_stats,
+            Action::ToggleVsync => self.toggle_vsync(ctx),
+            Action::NextFpsCap => self.next_fps_cap(),
+            Action::ToggleTouchMove => {
+                self.settings.touch_move = !self.settings.touch_move;
+                self.save_settings();
+                println!("Touch-move: {}", if self.settings.touch_move { "On" } else { "Off" });
+            }
+            Action::ToggleSquareNames => {
+                self.settings.square_names = !self.settings.square_names;
+                self.save_settings();
+                println!("Square names: {}", if self.settings.square_names { "On" } else { "Off" });
+            }
+            Action::ToggleEffects => {
+                self.effects = !self.effects;
+                if !self.effects { self.celebration.clear(); }
+                println!("Effects: {}", if self.effects { "On" } else { "Off" });
+            }
+            Action::ToggleKeySheet => self.show_keys = !self.show_keys,
+            Action::TogglePresentation => self.toggle_presentation(ctx),
+            Action::CursorUp | Action::CursorDown | Action::CursorLeft | Action::CursorRight => {
+                //the cursor moves the way the board is shown, a flipped board has rank 1 at the top
+                let flipped = self.view_flipped();
+                if let Some(presentation) = &mut self.presentation {
+                    let cursor = presentation.cursor;
+                    let moved = match (action, flipped) {
+                        (Action::CursorUp, false) | (Action::CursorDown, true) => cursor.up(),
+                        (Action::CursorDown, false) | (Action::CursorUp, true) => cursor.down(),
+                        (Action::CursorLeft, false) | (Action::CursorRight, true) => cursor.left(),
+                        _ => cursor.right(),
+                    };
+                    presentation.cursor = moved.unwrap_or(cursor);
+                }
+            }
+            Action::MarkSquare => {
+                if let Some(presentation) = &mut self.presentation {
+                    let cursor = presentation.cursor;
+                    if presentation.marks.contains(&cursor) {
+                        presentation.marks.retain(|mark| *mark != cursor);
+                    } else {
+                        presentation.marks.push(cursor);
+                    }
+                }
+            }
+            Action::DrawArrow => {
+                if let Some(presentation) = &mut self.presentation {
+                    match presentation.arrow_from.take() {
+                        Some(from) => presentation.arrows.push((from, presentation.cursor)),
+                        None => presentation.arrow_from = Some(presentation.cursor),
+                    }
+                }
+            }
+            Action::ClearAnnotations => {
+                if let Some(presentation) = &mut self.presentation {
+                    presentation.marks.clear();
+                    presentation.arrows.clear();
+                    presentation.arrow_from = None;
+                }
+            }
+            Action::RecordStep => {
+                if let Some(presentation) = &mut self.presentation {
+                    presentation.note = Some(String::new());
+                    presentation.typing = false;
+                }
+            }
+            Action::PreviousStep | Action::NextStep => {
+                if let Some(view) = &mut self.lesson_view {
+                    view.step = if action == Action::NextStep {
+                        (view.step + 1).min(view.steps.len() - 1)
+                    } else {
+                        view.step.saturating_sub(1)
+                    };
+                    self.board = view.steps[view.step].board;
+                }
+            }
+            Action::CloseLesson => self.lesson_view = None,
+        }
+    }
+
+    /// Turns presentation mode on in fullscreen, or back off to the window.
+    fn toggle_presentation(&mut self, ctx: &mut Context) {
+        let result = if let Some(presentation) = self.presentation.take() {
+            self.save_lesson(&presentation.steps);
+            input::mouse::set_cursor_hidden(ctx, false);
+            graphics::set_fullscreen(ctx, conf::FullscreenType::Windowed).and_then(|_| self.set_ui_scale(ctx, self.settings.ui_scale))
+        } else {
+            self.presentation = Some(Presentation {
+                marks: vec![],
+                arrows: vec![],
+                cursor: chess::Square::E4,
+                arrow_from: None,
+                steps: vec![],
+                note: None,
+                typing: false,
+            });
+            //the laser pointer dot replaces the cursor
+            input::mouse::set_cursor_hidden(ctx, true);
+            graphics::set_fullscreen(ctx, conf::FullscreenType::Desktop)
+        };
+        if let Err(e) = result {
+            println!("Failed to change the window for presentation mode: {}", e);
+        }
+        println!("Presentation mode: {}", if self.presentation.is_some() { "On" } else { "Off" });
+    }
+
+    /// Adds the shown position with its marks, arrows and the typed note as a lesson step.
+    fn record_step(&mut self) {
+        if let Some(presentation) = &mut self.presentation {
+            presentation.steps.push(lesson::Step {
+                board: self.board,
+                marks: presentation.marks.clone(),
+                arrows: presentation.arrows.clone(),
+                note: presentation.note.take().unwrap_or_default(),
+            });
+            println!("Recorded lesson step {}", presentation.steps.len());
+        }
+    }
+
+    /// Saves recorded lesson steps as a new .lesson file in the lessons directory.
+    fn save_lesson(&self, steps: &[lesson::Step]) {
+        if steps.is_empty() {
+            return;
+        }
+        let seconds = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = self.lessons_dir.join(format!("lesson-{}.lesson", seconds));
+        match std::fs::create_dir_all(&self.lessons_dir).and_then(|_| std::fs::write(&path, lesson::write(steps))) {
+            Ok(()) => println!("Saved the lesson with {} steps to {}", steps.len(), path.display()),
+            Err(e) => println!("Failed to save the lesson {}: {}", path.display(), e),
+        }
+    }
+
+    /// Opens a lesson from the text of a .lesson file at its first step, only between games.
+    fn open_lesson(&mut self, path: &str, text: &str) {
+        if self.status != BoardStatus::Checkmate {
+            println!("Finish the game before opening a lesson.");
+            return;
+        }
+        match lesson::read(text) {
+            Ok(steps) => {
+                self.replay_turn = 999;
+                self.board = steps[0].board;
+                println!("Opened the lesson {} with {} steps.", path, steps.len());
+                self.lesson_view = Some(LessonView { steps, step: 0 });
+            }
+            Err(e) => println!("Failed to read the lesson {}: {}", path, e),
+        }
+    }
+
+    /// Draws the marks and arrows of the lesson step and its note in place of the menu.
+    fn draw_lesson(&self, ctx: &mut Context) -> GameResult {
+        let view = match &self.lesson_view {
+            Some(view) => view,
+            None => return Ok(()),
+        };
+        let step = &view.steps[view.step];
+        annotation_overlay(&step.marks, &step.arrows).draw(ctx, self.view_flipped(), self.font)?;
+
+        let x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+        let panel = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(x, 20.0, 340.0, 8.0 * GRID_CELL_SIZE.1 as f32),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &panel, graphics::DrawParam::default())?;
+
+        let title = styled_text(&format!("Lesson step {}/{}", view.step + 1, view.steps.len()), self.font, TEXT_MEDIUM);
+        graphics::draw(ctx, &title, graphics::DrawParam::default().dest([x + 20.0, 40.0]))?;
+
+        //the note wraps inside the panel
+        let mut note = styled_text(&step.note, self.font, TEXT_NORMAL);
+        note.set_bounds([300.0, 560.0], graphics::Align::Left);
+        graphics::draw(ctx, &note, graphics::DrawParam::default().dest([x + 20.0, 100.0]))?;
+
+        let keys = styled_text("Left/Right = Step   Esc = Close", self.font, TEXT_SMALL);
+        graphics::draw(ctx, &keys, graphics::DrawParam::default().dest([x + 20.0, 8.0 * GRID_CELL_SIZE.1 as f32 - 20.0]))
+    }
+
+    /// Draws the keys of the analysis board between the name plates, and the FEN and PGN when they are shown.
+    fn draw_analysis(&self, ctx: &mut Context) -> GameResult {
+        if !self.analysis || self.board_only() {
+            return Ok(());
+        }
+        let x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+
+        let title = styled_text("Analysis board", self.font, TEXT_MEDIUM);
+        graphics::draw(ctx, &title, graphics::DrawParam::default().dest([x + 20.0, 100.0]))?;
+        let keys = styled_text("Backspace = Take back\nX = FEN/PGN   Esc = Close", self.font, TEXT_SMALL);
+        graphics::draw(ctx, &keys, graphics::DrawParam::default().dest([x + 20.0, 140.0]))?;
+        if !self.show_position {
+            return Ok(());
+        }
+
+        //only the moves of the PGN fit, the tags are the same for every analysis
+        let pgn = self.game_pgn();
+        let moves = pgn.split_once("\n\n").map_or(pgn.as_str(), |(_, moves)| moves);
+        let mut position = styled_text(&format!("{}\n\n{}", self.board, moves.trim()), self.font, TEXT_SMALL);
+        position.set_bounds([300.0, 8.0 * GRID_CELL_SIZE.1 as f32 - 280.0], graphics::Align::Left);
+        graphics::draw(ctx, &position, graphics::DrawParam::default().dest([x + 20.0, 200.0]))
+    }
+
+    /// Draws the pieces a pawn can promote to over the board, from the promotion square towards the middle.
+    fn draw_promotion_picker(&self, ctx: &mut Context) -> GameResult {
+        let (from, to) = match self.promotion_choice {
+            Some(choice) => choice,
+            None => return Ok(()),
+        };
+        let color = self.board.color_on(from).unwrap_or(self.side_to_move);
+
+        //the rest of the board is dimmed while the piece is picked
+        let board = graphics::Rect::new(20.0, 20.0, 8.0 * GRID_CELL_SIZE.0 as f32, 8.0 * GRID_CELL_SIZE.1 as f32);
+        let dim = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), board, graphics::Color::new(0.0, 0.0, 0.0, 0.5))?;
+        graphics::draw(ctx, &dim, graphics::DrawParam::default())?;
+
+        let regions = self.hit_regions(ctx);
+        let pos = self.mouse_position(ctx);
+        for (i, (piece, rect)) in promotion_rects(to, self.view_flipped()).into_iter().enumerate() {
+            let hovered = regions.hovered(HitRegion::PromotionPiece(i), pos.x, pos.y);
+            let background = if hovered { graphics::Color::new(1.0, 0.85, 0.4, 1.0) } else { WHITE };
+            let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [rect.x + rect.w / 2.0, rect.y + rect.h / 2.0], rect.w / 2.0, 0.5, background)?;
+            graphics::draw(ctx, &circle, graphics::DrawParam::default())?;
+            let sprite = &self.sprites[&(color, piece)];
+            graphics::draw(
+                ctx,
+                sprite,
+                graphics::DrawParam::default()
+                    .scale([rect.w / sprite.width() as f32, rect.h / sprite.height() as f32])
+                    .dest([rect.x, rect.y]),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws the marks and arrows of presentation mode, the square cursor and the laser pointer.
+    fn draw_presentation(&self, ctx: &mut Context) -> GameResult {
+        let presentation = match &self.presentation {
+            Some(presentation) => presentation,
+            None => return Ok(()),
+        };
+        let flipped = self.view_flipped();
+        annotation_overlay(&presentation.marks, &presentation.arrows).draw(ctx, flipped, self.font)?;
+
+        //the cursor is a thick frame, in another color while an arrow is being drawn
+        let cursor_color = if presentation.arrow_from.is_some() { graphics::Color::new(1.0, 0.65, 0.0, 1.0) } else { graphics::Color::new(0.1, 0.6, 1.0, 1.0) };
+        let cursor = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(6.0), square_rect(presentation.cursor, flipped), cursor_color)?;
+        graphics::draw(ctx, &cursor, graphics::DrawParam::default())?;
+        if let Some(from) = presentation.arrow_from {
+            let start = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), square_center(from, flipped), 12.0, 0.5, cursor_color)?;
+            graphics::draw(ctx, &start, graphics::DrawParam::default())?;
+        }
+
+        //the laser pointer is a red dot with a soft glow around it
+        let pos = self.mouse_position(ctx);
+        let glow = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), pos, 22.0, 0.5, graphics::Color::new(1.0, 0.0, 0.0, 0.25))?;
+        let dot = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), pos, 8.0, 0.5, graphics::Color::new(1.0, 0.1, 0.1, 1.0))?;
+        graphics::draw(ctx, &glow, graphics::DrawParam::default())?;
+        graphics::draw(ctx, &dot, graphics::DrawParam::default())?;
+
+        //the note of the step being recorded is typed in a bar over the bottom of the board
+        if let Some(note) = &presentation.note {
+            let bar = graphics::Mesh::new_rounded_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(40.0, 640.0, 680.0, 80.0),
+                5.0,
+                MENU_COLOR,
+            )?;
+            graphics::draw(ctx, &bar, graphics::DrawParam::default())?;
+            let lines = [
+                (format!("Note for step {}: {}_", presentation.steps.len() + 1, note), TEXT_NORMAL),
+                ("Enter = Record  Esc = Cancel".to_string(), TEXT_SMALL),
+            ];
+            for (i, (line, size)) in lines.iter().enumerate() {
+                let text = styled_text(line, self.font, *size);
+                graphics::draw(ctx, &text, graphics::DrawParam::default().dest([60.0, 652.0 + 32.0 * i as f32]))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws the key bindings that work right now over the board, grouped by where they work.
+    fn draw_key_sheet(&self, ctx: &mut Context) -> GameResult {
+        //the contexts in the order their first binding is listed, so every context with a binding shows up
+        let mut contexts: Vec<KeyContext> = vec![];
+        for binding in KEY_BINDINGS.iter() {
+            if !contexts.contains(&binding.context) {
+                contexts.push(binding.context);
+            }
+        }
+        //keys and descriptions are two columns with the same lines, the headings leave the description empty
+        let (mut keys, mut descriptions) = (String::new(), String::new());
+        for context in contexts.iter().filter(|context| self.key_context_active(**context)) {
+            keys.push_str(&format!("{}\n", context.name()));
+            descriptions.push('\n');
+            for binding in KEY_BINDINGS.iter().filter(|binding| binding.context == *context) {
+                let key = if binding.command { format!("{}+{}", COMMAND_NAME, binding.name) } else { binding.name.to_string() };
+                keys.push_str(&format!("    {}\n", key));
+                descriptions.push_str(&format!("{}\n", binding.description));
+            }
+        }
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(20.0, 20.0, GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32, GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32),
+            BADGE_COLOR,
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx, &styled_text(&keys, self.font, TEXT_SMALL), graphics::DrawParam::default().dest([40.0, 40.0]))?;
+        graphics::draw(ctx, &styled_text(&descriptions, self.font, TEXT_SMALL), graphics::DrawParam::default().dest([170.0, 40.0]))
+    }
+
+    /// Draws the evaluation bar between the board and the menu, White's share grows from White's side.
+    fn draw_eval_bar(&self, ctx: &mut Context) -> GameResult {
+        let score = eval::evaluate(&self.board);
+        let board_size = GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+
+        //turns the centipawn score into how much of the bar is white
+        let white_share = 1.0 / (1.0 + (-score as f32 / 400.0).exp());
+
+        //White's part starts at White's side of the board
+        let white_top = if self.view_flipped() { 20.0 } else { 20.0 + board_size * (1.0 - white_share) };
+
+        let black_part = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(board_size + 22.0, 20.0, 16.0, board_size),
+            graphics::Color::new(0.15, 0.15, 0.15, 1.0),
+        )?;
+        graphics::draw(ctx, &black_part, graphics::DrawParam::default())?;
+
+        let white_part = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                board_size + 22.0,
+                white_top,
+                16.0,
+                board_size * white_share,
+            ),
+            graphics::Color::new(0.95, 0.95, 0.95, 1.0),
+        )?;
+        graphics::draw(ctx, &white_part, graphics::DrawParam::default())?;
+
+        let score_text = styled_text(&format!("{:+.1}", score as f32 / 100.0), self.font, TEXT_SMALL);
+        graphics::draw(
+            ctx,
+            &score_text,
+            graphics::DrawParam::default().dest([board_size + 18.0, board_size + 22.0]),
+        )?;
+
+        Ok(())
+    }
+
+    /// Draws the name plate of one side in the menu, with name, rating, avatar and captured pieces.
+    /// The plate of the side to move is lit up.
+    fn draw_name_plate(&self, ctx: &mut Context, color: Color, y: f32) -> GameResult {
+        let x = 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+        let player = &self.players[color.to_index()];
+
+        let to_move = self.status == BoardStatus::Ongoing && self.side_to_move == color;
+        let plate = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(x, y, 340.0, 60.0),
+            5.0,
+            if to_move { graphics::Color::new(1.0, 1.0, 1.0, 1.0) } else { graphics::Color::new(0.7, 0.7, 0.7, 1.0) },
+        )?;
+        graphics::draw(ctx, &plate, graphics::DrawParam::default())?;
+
+        let mut text_x = x + 10.0;
+        if let Some(avatar) = &player.avatar {
+            graphics::draw(
+                ctx,
+                avatar,
+                graphics::DrawParam::default()
+                    .scale([50.0 / avatar.width() as f32, 50.0 / avatar.height() as f32])
+                    .dest([x + 5.0, y + 5.0]),
+            )?;
+            text_x = x + 62.0;
+        }
+
+        let mut name = match player.rating {
+            Some(rating) => format!("{} ({})", player.name, rating),
+            None => player.name.clone(),
+        };
+        if let Some(points) = self.match_points(color) {
+            name = format!("{}   {}", name, points);
+        }
+        let name_text = styled_text(&name, self.font, TEXT_NORMAL);
+        graphics::draw(
+            ctx,
+            &name_text,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([text_x, y + 5.0]),
+        )?;
+
+        //the opponent's pieces this player has taken, and the material lead if there is one
+        let captured = eval::captured_pieces(&self.board, !color);
+        for (i, piece) in captured.iter().enumerate() {
+            graphics::draw(
+                ctx,
+                self.sprites.get(&(!color, *piece)).unwrap(),
+                graphics::DrawParam::default()
+                    .scale([0.18, 0.18])
+                    .dest([text_x + i as f32 * 16.0, y + 32.0]),
+            )?;
+        }
+
+        let lead = captured.iter().map(|piece| eval::piece_value(*piece)).sum::<i32>()
+            - eval::captured_pieces(&self.board, color).iter().map(|piece| eval::piece_value(*piece)).sum::<i32>();
+        if lead > 0 {
+            let lead_text = styled_text(&format!("+{}", lead / 100), self.font, TEXT_SMALL);
+            graphics::draw(
+                ctx,
+                &lead_text,
+                graphics::DrawParam::default()
+                    .color([0.0, 0.0, 0.0, 1.0].into())
+                    .dest([text_x + captured.len() as f32 * 16.0 + 12.0, y + 36.0]),
+            )?;
+        }
+
+        Ok(())
+    }
 
-/// A chess board is 8x8 tiles.
-const GRID_SIZE: i16 = 8;
-/// Sutible size of each tile.
-const GRID_CELL_SIZE: (i16, i16) = (90, 90);
+    /// Draws the open confirmation dialog in the middle of the board.
+    fn draw_confirm(&self, ctx: &mut Context) -> GameResult {
+        let question = match self.confirm {
+            Some(Confirm::Resign) => format!("{:?}, do you want to resign?", self.side_to_move),
+            Some(Confirm::OfferDraw) => format!("{:?}, offer {:?} a draw?", self.side_to_move, !self.side_to_move),
+            Some(Confirm::AcceptDraw) => format!("{:?}, accept the draw offer?", !self.side_to_move),
+            None => return Ok(()),
+        };
 
-/// Size of the application window.
-const SCREEN_SIZE: (f32, f32) = (
-    GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 400.0,
-    GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32 + 40.0,
-);
+        let dialog = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(140.0, 310.0, 480.0, 140.0),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &dialog, graphics::DrawParam::default())?;
 
-// GUI Color representations
-const BLACK: graphics::Color =
-    graphics::Color::new(93.0 / 255.0, 50.0 / 255.0, 49.0 / 255.0, 1.0);
-const WHITE: graphics::Color =
-    graphics::Color::new(121.0 / 255.0, 71.0 / 255.0, 56.0 / 255.0, 1.0);
-const _CIRCLE_GRAY: graphics::Color =
-    graphics::Color::new(128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 0.8);
-const BACKGROUND_COLOR: graphics::Color =
-    graphics::Color::new(49.0 / 255.0, 46.0 / 255.0, 43.0 / 255.0, 1.0);
-const MENU_COLOR: graphics::Color =
-    graphics::Color::new(39.0 / 255.0, 37.0 / 255.0, 34.0 / 255.0, 1.0);    
+        let question_text = styled_text(&question, self.font, TEXT_MEDIUM);
+        let answer_text = styled_text("Y = Yes    N = No", self.font, TEXT_MEDIUM);
+        let question_width = question_text.width(ctx);
+        let answer_width = answer_text.width(ctx);
+        graphics::draw(
+            ctx,
+            &question_text,
+            graphics::DrawParam::default().dest([380.0 - question_width / 2.0, 340.0]),
+        )?;
+        graphics::draw(
+            ctx,
+            &answer_text,
+            graphics::DrawParam::default().dest([380.0 - answer_width / 2.0, 395.0]),
+        )?;
 
+        Ok(())
+    }
 
-/// GUI logic and event implementation structure.
-#[derive(Clone)]
-struct AppState {
-    sprites: HashMap<(Color, Piece), graphics::Image>,
-    // Example board representation.
-    board: Board,
-    // Imported game representation.
-    status: BoardStatus,
+    /// Draws why a FEN from another program was refused, one line per problem.
+    fn draw_position_problems(&self, ctx: &mut Context) -> GameResult {
+        let (fen, problems) = match &self.position_problems {
+            Some(refused) => refused,
+            None => return Ok(()),
+        };
 
-    game: Game,
+        let dialog = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(80.0, 160.0, 600.0, 440.0),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &dialog, graphics::DrawParam::default())?;
 
-    side_to_move: Color,
+        let title = styled_text("Illegal position", self.font, TEXT_MEDIUM);
+        let title_width = title.width(ctx);
+        graphics::draw(ctx, &title, graphics::DrawParam::default().dest([380.0 - title_width / 2.0, 180.0]))?;
 
-    pos_x: f32,
-    
-    pos_y: f32,
-    
-    piece: (Option<Color>, Option<Piece>),
+        let mut details = styled_text(&format!("{}\n\n{}", fen, problems.join("\n")), self.font, TEXT_SMALL);
+        details.set_bounds([560.0, 330.0], graphics::Align::Left);
+        graphics::draw(ctx, &details, graphics::DrawParam::default().dest([100.0, 225.0]))?;
 
-    saved_replay: Vec<Vec<Board>>,
+        let keys = styled_text("Send a legal FEN to start it   Esc = Close", self.font, TEXT_SMALL);
+        let keys_width = keys.width(ctx);
+        graphics::draw(ctx, &keys, graphics::DrawParam::default().dest([380.0 - keys_width / 2.0, 570.0]))
+    }
 
-    replay_boards: Vec<Board>,
+    /// Draws the current step of the first-run setup, the board behind it shows the choice.
+    fn draw_setup(&self, ctx: &mut Context) -> GameResult {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        let (question, choice) = match self.setup {
+            Some(SetupStep::Texture) => ("Board texture", self.theme.texture_name().unwrap_or_else(|| "Plain".to_string())),
+            Some(SetupStep::SquareNames) => ("Show square names", on_off(self.settings.square_names).to_string()),
+            Some(SetupStep::TouchMove) => ("Touch-move rule", on_off(self.settings.touch_move).to_string()),
+            None => return Ok(()),
+        };
+        let step = self.setup.map_or(0, |step| step.number());
 
-    replay_turn: usize,
+        let dialog = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(140.0, 290.0, 480.0, 180.0),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &dialog, graphics::DrawParam::default())?;
 
+        let lines = [
+            (format!("Welcome! Setup {}/3", step), TEXT_MEDIUM, 305.0),
+            (format!("{}: < {} >", question, choice), TEXT_MEDIUM, 355.0),
+            ("Left/Right change, Enter next, Escape skip".to_string(), TEXT_SMALL, 425.0),
+        ];
+        for (line, size, y) in lines.iter() {
+            let text = styled_text(line, self.font, *size);
+            let width = text.width(ctx);
+            graphics::draw(ctx, &text, graphics::DrawParam::default().dest([380.0 - width / 2.0, *y]))?;
+        }
 
+        Ok(())
+    }
 
-}
+    /// Draws the number of legal moves of each piece of the side to move, counted again after every move.
+    /// It is left out in training, puzzles and vision drills, where it would give away the answer.
+    fn draw_mobility(&mut self, ctx: &mut Context) -> GameResult {
+        if !self.mobility_hint || self.status == BoardStatus::Checkmate || self.training.is_some() || self.puzzle_play.is_some() || self.vision.is_some() {
+            return Ok(());
+        }
 
-impl AppState {
+        if self.mobility.as_ref().map_or(true, |(board, _)| *board != self.board) {
+            //a promotion counts once, not once per piece it can become
+            let mut counts: HashMap<chess::Square, usize> = HashMap::new();
+            for mv in MoveGen::new_legal(&self.board).filter(|mv| matches!(mv.get_promotion(), None | Some(Piece::Queen))) {
+                *counts.entry(mv.get_source()).or_insert(0) += 1;
+            }
+            let mut badges = Overlay::default();
+            for sq in *self.board.color_combined(self.board.side_to_move()) {
+                badges.badge(sq, &counts.get(&sq).unwrap_or(&0).to_string());
+            }
+            self.mobility = Some((self.board, badges));
+        }
 
-    /// Initialise new application, i.e. initialise new game and load resources.
-    fn new(ctx: &mut Context) -> GameResult<AppState> {
-        
-        let state = AppState {
-            sprites: AppState::load_sprites(ctx),
-            board:  Board::default(),
-            status: BoardStatus::Checkmate,
-            game: Game::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").expect("Valid FEN"),
-            side_to_move: Color::White,
-            pos_x: 355.0,
-            pos_y: 355.0,
-            piece: (None, None),
-            saved_replay: vec![],
-            replay_boards: vec![Board::default()],
-            replay_turn: 999,
+        match &self.mobility {
+            Some((_, badges)) => badges.draw(ctx, self.view_flipped(), self.font),
+            None => Ok(()),
+        }
+    }
+
+    /// Draws the open and half-open files, weak squares and outposts of the side to move, as a teaching aid.
+    /// Outposts get an N, they are the squares a knight wants.
+    fn draw_positional_hints(&self, ctx: &mut Context) -> GameResult {
+        if !self.positional_hints || self.status == BoardStatus::Checkmate || self.training.is_some() || self.puzzle_play.is_some() || self.vision.is_some() {
+            return Ok(());
+        }
+
+        let hints = structure::hints(&self.board, self.board.side_to_move());
+        let mut overlay = Overlay::default();
+        for sq in hints.open_files {
+            overlay.highlight(sq, graphics::Color::new(0.2, 0.6, 1.0, 0.22));
+        }
+        for sq in hints.half_open_files {
+            overlay.highlight(sq, graphics::Color::new(0.2, 0.6, 1.0, 0.1));
+        }
+        for sq in hints.weak_squares {
+            overlay.highlight(sq, graphics::Color::new(1.0, 0.5, 0.0, 0.3));
+        }
+        for sq in hints.outposts {
+            overlay.highlight(sq, graphics::Color::new(0.1, 0.8, 0.2, 0.45));
+            overlay.badge(sq, "N");
+        }
+        overlay.draw(ctx, self.view_flipped(), self.font)
+    }
+
+    /// Draws the name of the square under the cursor next to it, and the move "e2-e4" while a piece is dragged.
+    fn draw_square_name(&self, ctx: &mut Context) -> GameResult {
+        if !self.settings.square_names {
+            return Ok(());
+        }
+        let pos = self.mouse_position(ctx);
+        let sq = match square_at(pos.x, pos.y, self.view_flipped()) {
+            Some(sq) => sq,
+            None => return Ok(()),
         };
 
-        Ok(state)
+        let dragging = input::mouse::cursor_grabbed(ctx) && self.status != BoardStatus::Checkmate;
+        let label = if dragging {
+            format!("{}-{}", screen_square(self.pos_y as usize, self.pos_x as usize, self.view_flipped()), sq)
+        } else {
+            sq.to_string()
+        };
+
+        let text = styled_text(&label, self.font, TEXT_SMALL);
+        let dimensions = text.dimensions(ctx);
+        //below and right of the cursor, like a tooltip, but kept on the board
+        let x = (pos.x + 16.0).min(20.0 + 8.0 * GRID_CELL_SIZE.0 as f32 - dimensions.w - 8.0);
+        let y = (pos.y + 20.0).min(20.0 + 8.0 * GRID_CELL_SIZE.1 as f32 - dimensions.h - 4.0);
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(x - 4.0, y - 2.0, dimensions.w + 8.0, dimensions.h + 4.0),
+            4.0,
+            BADGE_COLOR,
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx, &text, graphics::DrawParam::default().dest([x, y]))
     }
-    #[rustfmt::skip] // Skips formatting on this function (not recommended)
-    /// Loads chess piese images into hashmap, for ease of use.
-    fn load_sprites(ctx: &mut Context) -> HashMap<(Color, Piece), graphics::Image> {
-        [
-            ((Color::Black, Piece::King), "/black-king.png".to_string()),
-            ((Color::Black, Piece::Queen), "/black-queen.png".to_string()),
-            ((Color::Black, Piece::Rook), "/black-rook.png".to_string()),
-            ((Color::Black, Piece::Pawn), "/black-pawn.png".to_string()),
-            ((Color::Black, Piece::Bishop), "/black-bishop.png".to_string()),
-            ((Color::Black, Piece::Knight), "/black-knight.png".to_string()),
-            ((Color::White, Piece::King), "/white-king.png".to_string()),
-            ((Color::White, Piece::Queen), "/white-queen.png".to_string()),
-            ((Color::White, Piece::Rook), "/white-rook.png".to_string()),
-            ((Color::White, Piece::Pawn), "/white-pawn.png".to_string()),
-            ((Color::White, Piece::Bishop), "/white-bishop.png".to_string()),
-            ((Color::White, Piece::Knight), "/white-knight.png".to_string())
-        ]
-            .iter()
-            .map(|(piece, path)| {
-                (*piece, graphics::Image::new(ctx, path).unwrap())
-            })
-            .collect::<HashMap<(Color, Piece), graphics::Image>>()
+
+    /// Shows a short message at the bottom of the board.
+    fn show_toast(&mut self, message: &str) {
+        self.toast = Some((message.to_string(), TOAST_TIME));
+    }
+
+    /// Draws the toast message, fading out over its last half second.
+    fn draw_toast(&self, ctx: &mut Context) -> GameResult {
+        let (message, left) = match &self.toast {
+            Some(toast) => toast,
+            None => return Ok(()),
+        };
+
+        let alpha = (left / 0.5).min(1.0);
+        let mut text = styled_text(message, self.font, TEXT_NORMAL);
+        text.fragments_mut().iter_mut().for_each(|fragment| fragment.color = Some(graphics::Color::new(1.0, 1.0, 1.0, alpha)));
+        let (width, height) = (text.width(ctx), text.height(ctx));
+
+        let center = 20.0 + 4.0 * GRID_CELL_SIZE.0 as f32;
+        let bottom = 20.0 + 8.0 * GRID_CELL_SIZE.1 as f32 - 40.0;
+        let background = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(center - width / 2.0 - 15.0, bottom - height - 10.0, width + 30.0, height + 20.0),
+            5.0,
+            graphics::Color::new(MENU_COLOR.r, MENU_COLOR.g, MENU_COLOR.b, 0.85 * alpha),
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(ctx, &text, graphics::DrawParam::default().dest([center - width / 2.0, bottom - height]))
+    }
+
+    /// Why a piece on a square can't be picked up, None if it can.
+    /// All piece input goes through this, the board only lets the side to move pick up its pieces.
+    /// With touch-move, a touched piece that can move is the only one that can be picked up.
+    fn input_lock(&self, sq: chess::Square) -> Option<String> {
+        if self.status == BoardStatus::Checkmate {
+            return None;
+        }
+        match (self.board.color_on(sq), self.touched) {
+            (Some(color), _) if color != self.side_to_move => Some("Not your turn".to_string()),
+            (_, Some(touched)) if touched != sq => {
+                let piece = self.board.piece_on(touched).map_or("piece".to_string(), |piece| format!("{:?}", piece).to_lowercase());
+                Some(format!("Touch-move: move the {} on {}", piece, touched))
+            }
+            _ => None,
+        }
+    }
+
+    /// Marks a picked up piece as touched when touch-move is on and the piece has a legal move.
+    /// Only games count, training, puzzles, drills and the analysis board are left alone.
+    fn touch(&mut self, sq: chess::Square) {
+        let game = self.status == BoardStatus::Ongoing && self.training.is_none() && self.puzzle_play.is_none() && self.vision.is_none() && !self.analysis;
+        let can_move = MoveGen::new_legal(&self.board).any(|mv| mv.get_source() == sq);
+        if self.settings.touch_move && game && self.board.color_on(sq) == Some(self.side_to_move) && can_move {
+            self.touched = Some(sq);
+        }
+    }
+
+    /// Draws the dialog where a recorded puzzle gets its title and difficulty.
+    fn draw_puzzle_draft(&self, ctx: &mut Context) -> GameResult {
+        let draft = match &self.puzzle_draft {
+            Some(draft) => draft,
+            None => return Ok(()),
+        };
+
+        let dialog = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(140.0, 265.0, 480.0, 230.0),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &dialog, graphics::DrawParam::default())?;
+
+        //the field that is typed in has a cursor
+        let (title_cursor, themes_cursor) = if draft.editing_themes { ("", "_") } else { ("_", "") };
+        let lines = [
+            (format!("Title: {}{}", draft.puzzle.title, title_cursor), TEXT_MEDIUM),
+            (format!("Themes: {}{}", draft.themes, themes_cursor), TEXT_MEDIUM),
+            (format!("Difficulty: {:?} (Up/Down)", draft.difficulty), TEXT_MEDIUM),
+            (format!("{} moves. Tab = Next field  Enter = Save  Esc = Cancel", draft.puzzle.solution.len()), TEXT_SMALL),
+        ];
+        for (i, (line, size)) in lines.iter().enumerate() {
+            let text = styled_text(line, self.font, *size);
+            let width = text.width(ctx);
+            graphics::draw(ctx, &text, graphics::DrawParam::default().dest([380.0 - width / 2.0, 290.0 + 50.0 * i as f32]))?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws a board at any place and size, with some squares highlighted. White is always at the bottom.
+    fn draw_mini_board(&self, ctx: &mut Context, board: &Board, x: f32, y: f32, size: f32, highlights: BitBoard) -> GameResult {
+        let cell = size / 8.0;
+        let mut tiles = graphics::MeshBuilder::new();
+        for row in 0..8 {
+            for col in 0..8 {
+                let sq = screen_square(row, col, false);
+                let tile = graphics::Rect::new(x + col as f32 * cell, y + row as f32 * cell, cell, cell);
+                tiles.rectangle(graphics::DrawMode::fill(), tile, if (row + col) % 2 == 0 { WHITE } else { BLACK })?;
+                if highlights & BitBoard::from_square(sq) != BitBoard::new(0) {
+                    tiles.rectangle(graphics::DrawMode::fill(), tile, graphics::Color::new(1.0, 0.85, 0.2, 0.55))?;
+                }
+            }
+        }
+        let tiles = tiles.build(ctx)?;
+        graphics::draw(ctx, &tiles, graphics::DrawParam::default())?;
+
+        for sq in *board.combined() {
+            let piece = (board.color_on(sq).expect("Occupied square"), board.piece_on(sq).expect("Occupied square"));
+            let sprite = &self.sprites[&piece];
+            let row = 7 - sq.get_rank().to_index();
+            let col = sq.get_file().to_index();
+            graphics::draw(
+                ctx,
+                sprite,
+                graphics::DrawParam::default()
+                    .scale([cell / sprite.width() as f32, cell / sprite.height() as f32])
+                    .dest([x + col as f32 * cell, y + row as f32 * cell]),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws the marked and the shown position side by side over the board, with the squares that differ highlighted.
+    fn draw_compare(&self, ctx: &mut Context) -> GameResult {
+        let marked = match (self.marked_position, self.comparing) {
+            (Some(marked), true) => marked,
+            _ => return Ok(()),
+        };
+
+        let mut differs = BitBoard::new(0);
+        for sq in chess::ALL_SQUARES.iter() {
+            if marked.piece_on(*sq) != self.board.piece_on(*sq) || marked.color_on(*sq) != self.board.color_on(*sq) {
+                differs |= BitBoard::from_square(*sq);
+            }
+        }
+
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(20.0, 20.0, 8.0 * GRID_CELL_SIZE.0 as f32, 8.0 * GRID_CELL_SIZE.1 as f32),
+            BACKGROUND_COLOR,
+        )?;
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+
+        let size = 4.0 * GRID_CELL_SIZE.0 as f32 - 10.0;
+        let top = 20.0 + 4.0 * GRID_CELL_SIZE.1 as f32 - size / 2.0;
+        for (i, (label, board)) in [("Marked", marked), ("Shown", self.board)].iter().enumerate() {
+            let x = 20.0 + i as f32 * (size + 20.0);
+            self.draw_mini_board(ctx, board, x, top, size, differs)?;
+
+            let text = styled_text(label, self.font, TEXT_MEDIUM);
+            let width = text.width(ctx);
+            graphics::draw(ctx, &text, graphics::DrawParam::default().dest([x + size / 2.0 - width / 2.0, top - 40.0]))?;
+        }
+
+        let summary = styled_text(&format!("{} squares differ", differs.popcnt()), self.font, TEXT_NORMAL);
+        let width = summary.width(ctx);
+        graphics::draw(
+            ctx,
+            &summary,
+            graphics::DrawParam::default().dest([20.0 + 4.0 * GRID_CELL_SIZE.0 as f32 - width / 2.0, top + size + 20.0]),
+        )
+    }
+
+    /// Draws the analysis board button in the menu, under the replays button.
+    fn draw_analysis_button(&self, ctx: &mut Context, regions: &hit::HitRegions) -> GameResult {
+        let rect = match regions.rect(HitRegion::AnalysisButton) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let pos = self.mouse_position(ctx);
+
+        let analysis_button = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            rect,
+            button_color(regions.hovered(HitRegion::AnalysisButton, pos.x, pos.y)),
+        )?;
+        graphics::draw(ctx, &analysis_button, graphics::DrawParam::default())?;
+
+        let analysis_text = styled_text("Analysis board", self.font, TEXT_LARGE);
+        let analysis_width = analysis_text.width(ctx);
+        graphics::draw(
+            ctx,
+            &analysis_text,
+            graphics::DrawParam::default()
+                .color([0.0, 0.0, 0.0, 1.0].into())
+                .dest([rect.x + 170.0 - analysis_width / 2.0, rect.y + 15.0]),
+        )
+    }
+
+    /// Draws the progress of a running puzzle or game import.
+    fn draw_import_progress(&self, ctx: &mut Context) -> GameResult {
+        let (label, bytes_read, total_bytes) = if let Some(progress) = &self.puzzle_import {
+            let progress = progress.lock().expect("Import progress lock poisoned");
+            (format!("Importing puzzles: {} read, {} kept", progress.read, progress.kept), progress.bytes_read, progress.total_bytes)
+        } else if let Some(progress) = &self.game_import {
+            let progress = progress.lock().expect("Game import lock poisoned");
+            let label = format!("Games: {} new, {} duplicates, {} failed", progress.imported, progress.duplicates, progress.failed);
+            (label, progress.bytes_read, progress.total_bytes)
+        } else {
+            return Ok(());
+        };
+        let share = if total_bytes > 0 { bytes_read as f32 / total_bytes as f32 } else { 0.0 };
+
+        let dialog = graphics::Mesh::new_rounded_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(140.0, 310.0, 480.0, 140.0),
+            5.0,
+            MENU_COLOR,
+        )?;
+        graphics::draw(ctx, &dialog, graphics::DrawParam::default())?;
+
+        let text = styled_text(&label, self.font, TEXT_NORMAL);
+        let width = text.width(ctx);
+        graphics::draw(ctx, &text, graphics::DrawParam::default().dest([380.0 - width / 2.0, 335.0]))?;
+
+        let track = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            graphics::Rect::new(170.0, 390.0, 420.0, 24.0),
+            graphics::Color::WHITE,
+        )?;
+        graphics::draw(ctx, &track, graphics::DrawParam::default())?;
+        if share > 0.0 {
+            let bar = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(170.0, 390.0, 420.0 * share.min(1.0), 24.0),
+                graphics::Color::WHITE,
+            )?;
+            graphics::draw(ctx, &bar, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes images of the asset files read since the last frame, and placeholders for missing pieces when all are read.
+    fn load_assets(&mut self, ctx: &mut Context) {
+        let loader = match &mut self.loading {
+            Some(loader) => loader,
+            None => return,
+        };
+
+        for loaded in loader.take() {
+            let piece = SPRITE_FILES.iter().find(|(_, name)| *name == loaded.name).map(|(piece, _)| *piece);
+            let image = loaded.bytes.ok_or_else(|| GameError::ResourceNotFound(loaded.name.clone(), vec![]))
+                .and_then(|bytes| graphics::Image::from_bytes(ctx, &bytes));
+            match (piece, image) {
+                (Some(piece), Ok(image)) => { self.sprites.insert(piece, image); }
+                (_, Err(e)) => println!("Failed to load {}: {}", loaded.name, e),
+                (None, Ok(_)) => {}
+            }
+        }
+
+        if loader.finished() {
+            //a missing piece is drawn as a plain square in its color rather than stopping the game
+            for (piece, _) in SPRITE_FILES.iter() {
+                if !self.sprites.contains_key(piece) {
+                    let color = if piece.0 == Color::White { graphics::Color::WHITE } else { graphics::Color::BLACK };
+                    if let Ok(image) = graphics::Image::solid(ctx, 64, color) {
+                        self.sprites.insert(*piece, image);
+                    }
+                }
+            }
+            self.loading = None;
+        }
+    }
+
+    /// Draws the splash screen with a progress bar while the assets are read.
+    fn draw_splash(&self, ctx: &mut Context, progress: f32) -> GameResult {
+        graphics::clear(ctx, BACKGROUND_COLOR);
+
+        let title = styled_text("Schack", self.font, TEXT_HUGE);
+        let width = title.width(ctx);
+        graphics::draw(ctx, &title, graphics::DrawParam::default().dest([SCREEN_SIZE.0 / 2.0 - width / 2.0, SCREEN_SIZE.1 / 2.0 - 120.0]))?;
+
+        let bar = graphics::Rect::new(SCREEN_SIZE.0 / 2.0 - 200.0, SCREEN_SIZE.1 / 2.0, 400.0, 20.0);
+        let mesh = graphics::MeshBuilder::new()
+            .rectangle(graphics::DrawMode::stroke(2.0), bar, graphics::Color::WHITE)?
+            .rectangle(graphics::DrawMode::fill(), graphics::Rect::new(bar.x, bar.y, bar.w * progress, bar.h), graphics::Color::WHITE)?
+            .build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+
+        let label = styled_text("Loading pieces...", self.font, TEXT_NORMAL);
+        let width = label.width(ctx);
+        graphics::draw(ctx, &label, graphics::DrawParam::default().dest([SCREEN_SIZE.0 / 2.0 - width / 2.0, bar.y + 40.0]))
     }
 }
 
@@ -113,10 +3360,90 @@ impl AppState {
 impl event::EventHandler<GameError> for AppState {
     /// For updating game logic, which front-end doesn't handle.
     /// It won't be necessary to touch this unless you are implementing something that's not triggered by the user, like a clock
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
         
+        self.load_assets(ctx);
+
+        if self.frame_times.len() >= FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(timer::delta(ctx).as_secs_f32() * 1000.0);
+        self.celebration.update(timer::delta(ctx).as_secs_f32());
+        self.flip_timer = (self.flip_timer - timer::delta(ctx).as_secs_f32()).max(0.0);
+        if let Some((_, left)) = &mut self.toast {
+            *left -= timer::delta(ctx).as_secs_f32();
+        }
+        if self.toast.as_ref().map_or(false, |(_, left)| *left <= 0.0) {
+            self.toast = None;
+        }
+
+        self.run_commands();
+        self.kiosk_step(timer::delta(ctx).as_secs_f32());
+
+        //the vision drill runs against the clock
+        if self.status == BoardStatus::Ongoing {
+            if let Some(drill) = &mut self.vision {
+                drill.tick(timer::delta(ctx).as_secs_f32());
+            }
+            self.finish_vision();
+        }
+
+        //a move from another program while the window is in the background gets a notification and a taskbar flash
+        if let Some(message) = self.move_notification.take() {
+            notify::send("Schack", &message);
+            graphics::window(ctx).request_user_attention(Some(Default::default()));
+        }
+
+        //a finished puzzle import is reported once and its dialog closes
+        let import_done = self.puzzle_import.as_ref()
+            .map(|progress| progress.lock().expect("Import progress lock poisoned").clone())
+            .filter(|progress| progress.done);
+        if let Some(progress) = import_done {
+            match progress.error {
+                Some(e) => println!("Puzzle import failed after {} puzzles: {}", progress.kept, e),
+                None => println!("Imported {} of {} puzzles, {} were already saved.", progress.kept, progress.read, progress.duplicates),
+            }
+            self.puzzle_import = None;
+            self.puzzle_db = puzzles::load(&self.puzzles_path);
+        }
+
+        //imported games become replays as they are read, the report is printed when the import is done
+        let import_done = match &self.game_import {
+            Some(progress) => {
+                let mut progress = progress.lock().expect("Game import lock poisoned");
+                self.saved_replay.append(&mut progress.games);
+                self.imported_games.extend(progress.keys.drain(..));
+                if progress.done { Some(progress.clone()) } else { None }
+            }
+            None => None,
+        };
+        if let Some(progress) = import_done {
+            for error in progress.errors.iter() {
+                println!("{}", error);
+            }
+            if let Some(e) = &progress.error {
+                println!("Game import stopped: {}", e);
+            }
+            let report = format!(
+                "Imported {} games, skipped {} duplicates, {} failed",
+                progress.imported, progress.duplicates, progress.failed,
+            );
+            println!("{}.", report);
+            self.show_toast(&report);
+            self.game_import = None;
+        }
+
+        //sleeps away the rest of the frame when there is a frame limit
+        if let Some(cap) = self.settings.fps_cap {
+            let frame = Duration::from_secs_f32(1.0 / cap as f32);
+            let elapsed = self.last_frame.elapsed();
+            if elapsed < frame {
+                thread::sleep(frame - elapsed);
+            }
+        }
+        self.last_frame = Instant::now();
 
-        if input::keyboard::is_key_pressed(_ctx, input::keyboard::KeyCode::B)  {
+        if input::keyboard::is_key_pressed(ctx, input::keyboard::KeyCode::B)  {
             println!("x:{} y:{} -Up", self.pos_x, self.pos_y);
             println!("{:?}", self.piece);
 
@@ -127,18 +3454,15 @@ impl event::EventHandler<GameError> for AppState {
 
     /// Draw interface, i.e. draw game board
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(loader) = &self.loading {
+            self.draw_splash(ctx, loader.progress()).expect("Failed to draw splash screen.");
+            graphics::present(ctx).expect("Failed to update graphics.");
+            return Ok(());
+        }
+
         // clear interface with gray background Color
         graphics::clear(ctx, [0.5, 0.5, 0.5, 1.0].into());
 
-        // create text representation
-        let side_to_move_text = graphics::Text::new(
-            graphics::TextFragment::from(format!("{:?} to move...", self.side_to_move))
-                .scale(graphics::PxScale { x: 25.0, y: 25.0 }),
-        );
-
-        // get size of text
-        let text_dimensions = side_to_move_text.dimensions(ctx);
-        
         // create background rectangle with white coulouring
         let background_box = graphics::Mesh::new_rectangle(
             ctx,
@@ -149,13 +3473,24 @@ impl event::EventHandler<GameError> for AppState {
                 SCREEN_SIZE.0 as f32,
                 SCREEN_SIZE.1 as f32,
             ),
-            BACKGROUND_COLOR,
+            if self.stream_mode { STREAM_KEY_COLOR } else { BACKGROUND_COLOR },
         )?;
 
         // draw background
         graphics::draw(ctx, &background_box, graphics::DrawParam::default())
             .expect("Failed to draw background.");
 
+        // draw background image stretched over the window, if the theme has one
+        if let (Some(background), false) = (&self.theme.background, self.stream_mode) {
+            graphics::draw(
+                ctx,
+                background,
+                graphics::DrawParam::default()
+                    .scale([SCREEN_SIZE.0 / background.width() as f32, SCREEN_SIZE.1 / background.height() as f32]),
+            )
+            .expect("Failed to draw background.");
+        }
+
         let menu = graphics::Mesh::new_rounded_rectangle(
             ctx,
             graphics::DrawMode::fill(),
@@ -169,50 +3504,26 @@ impl event::EventHandler<GameError> for AppState {
             MENU_COLOR,
         )?;
     
-        // draw Menu
-        graphics::draw(ctx, &menu, graphics::DrawParam::default())
-            .expect("Failed to draw menu.");
-
-        
-        let side = graphics::Mesh::new_rounded_rectangle(
-            ctx,
-            graphics::DrawMode::fill(),
-            graphics::Rect::new(
-                40.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32),
-                20.0,
-                340.0,
-                60.0,
-            ),
-            5.0,
-            graphics::Color { r: (1.0), g: (1.0), b: (1.0), a: (1.0) },
-        )?;
-    
-        // draw Menu
-        graphics::draw(ctx, &side, graphics::DrawParam::default())
-            .expect("Failed to draw menu.");
+        // draw Menu, stream mode leaves the chroma key around the board
+        if !self.board_only() {
+            graphics::draw(ctx, &menu, graphics::DrawParam::default())
+                .expect("Failed to draw menu.");
+        }
 
 
         
-        //Start button and replay button
-        if self.status == BoardStatus::Checkmate {
-            let pos = input::mouse::position(ctx);
-            
+        //Start button and replay button, lit up under the mouse
+        let regions = self.hit_regions(ctx);
+        let pos = self.mouse_position(ctx);
+        if let (Some(start_rect), Some(replay_rect)) = (regions.rect(HitRegion::StartButton), regions.rect(HitRegion::ReplaysButton)) {
             // create text representation
-            let start_text = graphics::Text::new(
-            graphics::TextFragment::from(format!("Start Game"))
-                .scale(graphics::PxScale { x: 30.0, y: 30.0 }),
-            );
+            let start_text = styled_text("Start Game", self.font, TEXT_LARGE);
             
             let start_button = graphics::Mesh::new_rectangle(
                 ctx,
                 graphics::DrawMode::fill(),
-                graphics::Rect::new(
-                    40.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32),
-                    100.0,
-                    340.0,
-                    60.0,
-                ),
-                graphics::Color { r: (1.0), g: (1.0), b: (1.0), a: (1.0) },
+                start_rect,
+                button_color(regions.hovered(HitRegion::StartButton, pos.x, pos.y)),
             )?;
         
             // draw Menu
@@ -226,29 +3537,24 @@ impl event::EventHandler<GameError> for AppState {
             graphics::DrawParam::default()
                 .color([0.0, 0.0, 0.0, 1.0].into())
                 .dest(ggez::mint::Point2 {
-                    x:  120.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32) as f32,
-                    y: 120.0,
+                    x: start_rect.x + 80.0,
+                    y: start_rect.y + 20.0,
                 }),
             )
             .expect("Failed to draw text.");
             
             // create text representation
-            let replay_text = graphics::Text::new(
-                graphics::TextFragment::from(format!("Replays"))
-                    .scale(graphics::PxScale { x: 30.0, y: 30.0 }),
-                );
+            let replay_text = match &self.replay_search {
+                Some(search) => styled_text(&format!("Search: {}_", search), self.font, TEXT_NORMAL),
+                None => styled_text("Replays", self.font, TEXT_LARGE),
+            };
 
 
             let replay_button = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
-            graphics::Rect::new(
-                40.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32),
-                160.0,
-                340.0,
-                60.0,
-                ),
-                graphics::Color { r: (1.0), g: (1.0), b: (1.0), a: (1.0) },
+            replay_rect,
+            button_color(regions.hovered(HitRegion::ReplaysButton, pos.x, pos.y)),
             )?;
         
             // draw Menu
@@ -262,63 +3568,93 @@ impl event::EventHandler<GameError> for AppState {
                 graphics::DrawParam::default()
                     .color([0.0, 0.0, 0.0, 1.0].into())
                     .dest(ggez::mint::Point2 {
-                        x: 140.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32) as f32,
-                        y: 160.0,
+                        x: replay_rect.x + 100.0,
+                        y: replay_rect.y,
                     }),
                 )
                 .expect("Failed to draw text.");
 
-                if (pos.x >= 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 && pos.x <= 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 340.0) && (pos.y >= 160.0 && pos.y <= 220.0) {
-                    let replay_options = graphics::Mesh::new_rectangle(
+                self.draw_analysis_button(ctx, &regions).expect("Failed to draw analysis button.");
+
+                //the replay list, one row per game
+                let filtered = self.filtered_replays();
+                for (i, &index) in filtered.iter().take(REPLAY_LIST_ROWS).enumerate() {
+                    let row = match regions.rect(HitRegion::ReplayEntry(i)) {
+                        Some(row) => row,
+                        None => break,
+                    };
+                    let background = graphics::Mesh::new_rectangle(
                         ctx,
                         graphics::DrawMode::fill(),
-                        graphics::Rect::new(
-                            40.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32),
-                            220.0,
-                            340.0,
-                            30.0 * self.saved_replay.len() as f32,
-                        ),
-                        graphics::Color { r: (1.0), g: (1.0), b: (1.0), a: (1.0) },
+                        row,
+                        button_color(regions.hovered(HitRegion::ReplayEntry(i), pos.x, pos.y)),
                     )?;
-                
-                    // draw Menu
-                    graphics::draw(ctx, &replay_options, graphics::DrawParam::default())
+                    graphics::draw(ctx, &background, graphics::DrawParam::default())
                         .expect("Failed to draw menu.");
 
-                    // create text representation
-                    for i in 0..self.saved_replay.len() {
-                        let replays = graphics::Text::new(
-                        graphics::TextFragment::from(format!("{}: Game", i))
-                            .scale(graphics::PxScale { x: 30.0, y: 30.0 }),
-                        );
-                        //draw text with dark gray Coloring and center position
-                        graphics::draw(
-                            ctx,
-                            &replays,
-                            graphics::DrawParam::default()
-                                .color([0.0, 0.0, 0.0, 1.0].into())
-                                .dest(ggez::mint::Point2 {
-                                    x: 140.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32) as f32,
-                                    y: 180.0 + 10.0 * i as f32,
-                                }),
-                            )
-                            .expect("Failed to draw text.");
-                    }
-
-                    while self.status == BoardStatus::Ongoing {
-                        
+                    //the ECO code of the game when its opening is known, and the players when they are
+                    let replay = &self.saved_replay[index];
+                    let mut label = replay.opening().map_or("Game", |opening| opening.eco).to_string();
+                    if !replay.info().white.is_empty() || !replay.info().black.is_empty() {
+                        label = format!("{} {} - {}", label, replay.info().white, replay.info().black);
                     }
-        
-                } 
+                    let replays = styled_text(&format!("{}: {}", index, label), self.font, TEXT_NORMAL);
+                    //draw text with dark gray Coloring
+                    graphics::draw(
+                        ctx,
+                        &replays,
+                        graphics::DrawParam::default()
+                            .color([0.0, 0.0, 0.0, 1.0].into())
+                            .dest(ggez::mint::Point2 {
+                                x: row.x + 100.0,
+                                y: row.y + 4.0,
+                            }),
+                        )
+                        .expect("Failed to draw text.");
+                }
         }
 
 //Draws the whole chessboard
-        // draw grid
+        // squash the board while it is flipping
+        let flip_scale = self.flip_scale();
+        let view_flipped = self.view_flipped();
+        if flip_scale < 1.0 {
+            graphics::push_transform(ctx, Some(squash_transform(flip_scale)));
+            graphics::apply_transformations(ctx)?;
+        }
+
+        // draw the whole grid in one pass, the board stats count the draw calls
+        let mut board_draws = 0;
+        let mut light_batch = self.theme.light_texture.clone().map(graphics::spritebatch::SpriteBatch::new);
+        let mut dark_batch = self.theme.dark_texture.clone().map(graphics::spritebatch::SpriteBatch::new);
+        let mut piece_batches: HashMap<(Color, Piece), graphics::spritebatch::SpriteBatch> = HashMap::new();
+        let mut tiles = graphics::MeshBuilder::new();
+
         for row in 0..8 {
             for col in 0..8 {
-                // draw tile
-                let rectangle = graphics::Mesh::new_rectangle(
-                    ctx,
+                // texture under the tile
+                let (texture, batch) = if (row + col) % 2 == 0 {
+                    (&self.theme.light_texture, &mut light_batch)
+                } else {
+                    (&self.theme.dark_texture, &mut dark_batch)
+                };
+                if let (Some(texture), Some(batch)) = (texture, batch) {
+                    let scale = [
+                        GRID_CELL_SIZE.0 as f32 / texture.width() as f32,
+                        GRID_CELL_SIZE.1 as f32 / texture.height() as f32,
+                    ];
+                    batch.add(
+                        graphics::DrawParam::default()
+                            .scale(scale)
+                            .dest([
+                                col as f32 * GRID_CELL_SIZE.0 as f32 + 20.0,
+                                row as f32 * GRID_CELL_SIZE.1 as f32 + 20.0,
+                            ]),
+                    );
+                }
+
+                // tile
+                tiles.rectangle(
                     graphics::DrawMode::fill(),
                     graphics::Rect::new_i32(
                         col * GRID_CELL_SIZE.0 as i32 + 20,
@@ -326,7 +3662,7 @@ impl event::EventHandler<GameError> for AppState {
                         GRID_CELL_SIZE.0 as i32,
                         GRID_CELL_SIZE.1 as i32,
                     ),
-                    match col % 2 {
+                    self.theme.tile_color(match col % 2 {
                         0 => {
                             if row % 2 == 0 {
                                 WHITE
@@ -341,55 +3677,152 @@ impl event::EventHandler<GameError> for AppState {
                                 WHITE
                             }
                         }
-                    },
-                )
-                .expect("Failed to create tile.");
-                graphics::draw(ctx, &rectangle, graphics::DrawParam::default())
-                    .expect("Failed to draw tiles.");
+                    }),
+                )?;
 
-                
-                // draw all the piecess
-                let sq = chess::Square::make_square(chess::Rank::from_index(7-row as usize), chess::File::from_index(col as usize));
-                let piece = (self.board.color_on(sq), self.board.piece_on(sq));
-                if piece.1 != None {
-                    let pieces = (self.board.color_on(sq).unwrap(), self.board.piece_on(sq).unwrap());
-                    graphics::draw(
-                        ctx,
-                        self.sprites.get(&pieces).unwrap(),
-                        graphics::DrawParam::default()
-                            .scale([0.625, 0.625]) // Tile size is 110 pixels, while image sizes are 440 pixels.
-                            .dest([
-                                col as f32 * GRID_CELL_SIZE.0 as f32 + 25.0,
-                                row as f32 * GRID_CELL_SIZE.1 as f32 + 25.0,
-                            ]),
-                    )
-                    .expect("Failed to draw piece.");
+                // piece, one batch for each kind of piece
+                let sq = screen_square(row as usize, col as usize, view_flipped);
+                if let (Some(color), Some(piece)) = (self.board.color_on(sq), self.board.piece_on(sq)) {
+                    let sprites = &self.sprites;
+                    piece_batches
+                        .entry((color, piece))
+                        .or_insert_with(|| graphics::spritebatch::SpriteBatch::new(sprites[&(color, piece)].clone()))
+                        .add(
+                            graphics::DrawParam::default()
+                                .scale([0.625, 0.625]) // Tile size is 110 pixels, while image sizes are 440 pixels.
+                                .dest([
+                                    col as f32 * GRID_CELL_SIZE.0 as f32 + 25.0,
+                                    row as f32 * GRID_CELL_SIZE.1 as f32 + 25.0,
+                                ]),
+                        );
                 }
             }
         }
 
+        for batch in [&light_batch, &dark_batch].iter().filter_map(|batch| batch.as_ref()) {
+            graphics::draw(ctx, batch, graphics::DrawParam::default())
+                .expect("Failed to draw texture.");
+            board_draws += 1;
+        }
+        let tiles = tiles.build(ctx)?;
+        graphics::draw(ctx, &tiles, graphics::DrawParam::default())
+            .expect("Failed to draw tiles.");
+        board_draws += 1;
+        for batch in piece_batches.values() {
+            graphics::draw(ctx, batch, graphics::DrawParam::default())
+                .expect("Failed to draw piece.");
+            board_draws += 1;
+        }
 
-//draw the text for who turn it is
-        graphics::draw(
-            ctx,
-            &side_to_move_text,
-            graphics::DrawParam::default()
-                .color([0.0, 0.0, 0.0, 1.0].into())
-                .dest(ggez::mint::Point2 {
-                    x:  100.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32) as f32,
-                    y: 35.0,
-                }),
-        )
-        .expect("Failed to draw text.");
+        if flip_scale < 1.0 {
+            graphics::pop_transform(ctx);
+            graphics::apply_transformations(ctx)?;
+        }
+        self.board_draws = board_draws;
+
+        if self.board_only() {
+            self.draw_last_move(ctx).expect("Failed to draw last move.");
+        } else {
+            self.draw_eval_bar(ctx).expect("Failed to draw evaluation bar.");
+        }
+
+//draw the name plates, the player at the top of the board gets the top plate
+        let top_color = if self.view_flipped() { Color::White } else { Color::Black };
+        self.draw_name_plate(ctx, top_color, 20.0).expect("Failed to draw name plate.");
+        self.draw_name_plate(ctx, !top_color, 20.0 + 8.0 * GRID_CELL_SIZE.1 as f32 - 60.0)
+            .expect("Failed to draw name plate.");
+
+//draw the UI scale slider
+        if !self.board_only() {
+            self.draw_scale_slider(ctx).expect("Failed to draw slider.");
+        }
+
+//draw the rematch button
+        self.draw_rematch_button(ctx, &regions).expect("Failed to draw rematch button.");
+
+//draw how the last game ended, or how the vision drill is going
+        let result = match (&self.result, self.status, &self.vision) {
+            (Some(result), BoardStatus::Checkmate, _) => Some(result.clone()),
+            (_, BoardStatus::Ongoing, Some(drill)) => Some(drill.summary()),
+            _ => None,
+        };
+        if let Some(result) = result {
+            let result_text = styled_text(result.as_str(), self.font, TEXT_MEDIUM);
+            let result_width = result_text.width(ctx);
+            graphics::draw(
+                ctx,
+                &result_text,
+                graphics::DrawParam::default().dest([
+                    40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 170.0 - result_width / 2.0,
+                    8.0 * GRID_CELL_SIZE.1 as f32 - 80.0,
+                ]),
+            )
+            .expect("Failed to draw text.");
+        }
+
+//the puzzle picker between games, and the puzzle that is being solved
+        if self.status == BoardStatus::Checkmate && !self.board_only() {
+            let theme = self.puzzle_theme.clone().unwrap_or_else(|| "All".to_string());
+            let lines = [
+                format!("Puzzles: {} ({})", theme, self.filtered_puzzles().len()),
+                "Left/Right = Theme   U = Solve".to_string(),
+            ];
+            for (i, line) in lines.iter().enumerate() {
+                let text = styled_text(line, self.font, if i == 0 { TEXT_NORMAL } else { TEXT_SMALL });
+                let width = text.width(ctx);
+                graphics::draw(
+                    ctx,
+                    &text,
+                    graphics::DrawParam::default().dest([
+                        40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 170.0 - width / 2.0,
+                        395.0 + 28.0 * i as f32,
+                    ]),
+                )
+                .expect("Failed to draw text.");
+            }
+        }
+        if let (Some(play), BoardStatus::Ongoing) = (&self.puzzle_play, self.status) {
+            let puzzle_text = styled_text(
+                &format!("{} ({}), {:?} to move", play.puzzle.title, play.puzzle.rating, self.side_to_move),
+                self.font,
+                TEXT_NORMAL,
+            );
+            let puzzle_width = puzzle_text.width(ctx);
+            graphics::draw(
+                ctx,
+                &puzzle_text,
+                graphics::DrawParam::default().dest([
+                    40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 170.0 - puzzle_width / 2.0,
+                    8.0 * GRID_CELL_SIZE.1 as f32 - 80.0,
+                ]),
+            )
+            .expect("Failed to draw text.");
+        }
+
+//show that a puzzle is being recorded
+        if let (Some((_, start)), BoardStatus::Ongoing) = (self.puzzle_start, self.status) {
+            let moves = self.played_moves().len().saturating_sub(start);
+            let recording_text = styled_text(&format!("Recording puzzle: {} moves (P)", moves), self.font, TEXT_NORMAL);
+            let recording_width = recording_text.width(ctx);
+            graphics::draw(
+                ctx,
+                &recording_text,
+                graphics::DrawParam::default().dest([
+                    40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 170.0 - recording_width / 2.0,
+                    8.0 * GRID_CELL_SIZE.1 as f32 - 80.0,
+                ]),
+            )
+            .expect("Failed to draw text.");
+        }
             
 //Draws the pieces on the cursor when grabbing the mouse, also draws the possible moves
             if input::mouse::cursor_grabbed(ctx) == true && self.status != BoardStatus::Checkmate {
 
                 //Gets the current position of the mouse 
-                let pos = input::mouse::position(ctx);
+                let pos = self.mouse_position(ctx);
 
                 //creates a square at the clicked position and maybe finds piece on that square
-                let sq = chess::Square::make_square(chess::Rank::from_index(7-self.pos_y as usize), chess::File::from_index(self.pos_x as usize));
+                let sq = screen_square(self.pos_y as usize, self.pos_x as usize, self.view_flipped());
                 self.piece = (self.board.color_on(sq), self.board.piece_on(sq));
 
                 //only if their exists a piece on the square and the color is the current side to move.
@@ -425,17 +3858,13 @@ impl event::EventHandler<GameError> for AppState {
                     for x in bb  {
                         let r = 7-x.get_rank().to_index(); 
                         let f = x.get_file().to_index();
+                        let x_rect = square_rect(x, self.view_flipped());
 
                             //possible moves square mesh and draws them
                             let rectangle = graphics::Mesh::new_rectangle(
                                 ctx,
                                 graphics::DrawMode::fill(),
-                                graphics::Rect::new_i32(
-                                    f as i32 * GRID_CELL_SIZE.0 as i32 + 20,
-                                    r as i32 * GRID_CELL_SIZE.0 as i32 + 20,
-                                    GRID_CELL_SIZE.0 as i32,
-                                    GRID_CELL_SIZE.1 as i32,
-                                ),
+                                x_rect,
                                 match (f as i32) % 2 {
                                     0 => {
                                         if  (r as i32) % 2 == 0 {
@@ -464,12 +3893,7 @@ impl event::EventHandler<GameError> for AppState {
                             let rectangle = graphics::Mesh::new_rectangle(
                                 ctx,
                                 graphics::DrawMode::fill(),
-                                graphics::Rect::new_i32(
-                                    ef as i32 * GRID_CELL_SIZE.0 as i32 + 20,
-                                    er as i32 * GRID_CELL_SIZE.0 as i32 + 20,
-                                    GRID_CELL_SIZE.0 as i32,
-                                    GRID_CELL_SIZE.1 as i32,
-                                ),
+                                square_rect(en_sq, self.view_flipped()),
                                 match (ef as i32) % 2 {
                                     0 => {
                                         if  (er as i32) % 2 == 0 {
@@ -501,10 +3925,7 @@ impl event::EventHandler<GameError> for AppState {
                                 self.sprites.get(&pieces).unwrap(),
                                 graphics::DrawParam::default()
                                     .scale([0.625, 0.625]) // Tile size is 110 pixels, while image sizes are 440 pixels.
-                                    .dest([
-                                        f as f32 * GRID_CELL_SIZE.0 as f32 + 25.0,
-                                        r as f32 * GRID_CELL_SIZE.1 as f32 + 25.0,
-                                    ]),
+                                    .dest([x_rect.x + 5.0, x_rect.y + 5.0]),
                             )
                             .expect("Failed to draw piece.");
                     }
@@ -526,6 +3947,18 @@ impl event::EventHandler<GameError> for AppState {
                     graphics::draw(ctx, &rectangle, graphics::DrawParam::default())
                         .expect("Failed to draw tiles.");
 
+                    //Draws faint arrows from the enemy pieces that attack the possible moves
+                    if self.attack_arrows {
+                        let mut hints = Overlay::default();
+                        let blockers = *self.board.combined() & !BitBoard::from_square(sq);
+                        for x in bb {
+                            for attacker in attackers(&self.board, x, !self.side_to_move, blockers) {
+                                hints.arrow(attacker, x, graphics::Color::new(1.0, 0.55, 0.0, 0.45));
+                            }
+                        }
+                        hints.draw(ctx, self.view_flipped(), self.font).expect("Failed to draw attack arrows.");
+                    }
+
                     //Draws the grabbed piece on the mouse 
                     let pieces = (self.board.color_on(sq).unwrap(), self.board.piece_on(sq).unwrap());
                     graphics::draw(
@@ -549,11 +3982,19 @@ impl event::EventHandler<GameError> for AppState {
             if input::mouse::cursor_grabbed(ctx) == false && self.piece != (None, None) && self.piece.0 == Some(self.side_to_move) && self.status != BoardStatus::Checkmate {
 
                 //current position of mouse
-                let pos = input::mouse::position(ctx);
+                let pos = self.mouse_position(ctx);
 
                 //Finds the from and to square of the grabbed piece
-                let from_sq = chess::Square::make_square(chess::Rank::from_index(7-self.pos_y as usize), chess::File::from_index(self.pos_x as usize));
-                let to_sq = chess::Square::make_square(chess::Rank::from_index(7-((pos.y-20.0)/GRID_CELL_SIZE.0 as f32).floor() as usize), chess::File::from_index(((pos.x-20.0)/GRID_CELL_SIZE.0 as f32).floor() as usize));
+                let from_sq = screen_square(self.pos_y as usize, self.pos_x as usize, self.view_flipped());
+                //dropping outside the board puts the piece back
+                let mut to_sq = square_at(pos.x, pos.y, self.view_flipped()).unwrap_or(from_sq);
+
+                //slip protection puts the piece back when it was only dragged a little
+                let dragged = ((pos.x - self.grab_point[0]).powi(2) + (pos.y - self.grab_point[1]).powi(2)).sqrt();
+                if to_sq != from_sq && dragged < self.settings.min_drag as f32 {
+                    self.show_toast("Drag further to move, slip protection is on");
+                    to_sq = from_sq;
+                }
 
 
                 let mut promotion = None;
@@ -565,15 +4006,12 @@ impl event::EventHandler<GameError> for AppState {
                 //Creates a move out of the from and to square aswell as the possible promotion.
                 let mv = chess::ChessMove::new(from_sq, to_sq, promotion);
                 
+                //a legal promotion waits until the piece is picked, an illegal one is explained like any move
+                if promotion.is_some() && self.board.legal(mv) {
+                    self.promotion_choice = Some((from_sq, to_sq));
+                }
                 //Only works if the created moves actually is legal.
-                if self.game.make_move(mv) == true {
-
-                    //Updates board and status
-                    self.board = self.game.current_position();
-                    self.status = self.board.status();
-
-                    //Saves the the board for replay after game has ended
-                    self.replay_boards.push(self.board);
+                else if self.play_move(mv) {
 
                     //Draws a square over the moved pieces origin position for fanciness
                     let rectangle = graphics::Mesh::new_rectangle(
@@ -605,34 +4043,72 @@ impl event::EventHandler<GameError> for AppState {
                     graphics::draw(ctx, &rectangle, graphics::DrawParam::default())
                         .expect("Failed to draw tiles.");
 
-                    println!("{:?} move: {}\nboard: {}\nStatus: {:?}", self.side_to_move, mv, self.board, self.status);
-                    
-                    if self.status == BoardStatus::Checkmate {
-                        match self.side_to_move {
-                            Color::White => println!("White Won by Checkmate!"),
-                            Color::Black => println!("Black Won by Checkmate!"),
-                        }
-
-                        //Saves the moves to the replay vector.
-                        self.saved_replay.push(self.replay_boards.clone());
-                        
-                       
-                    } else { self.side_to_move = !self.side_to_move; }
-
+                } else if let Some(reason) = legality::explain(&self.board, mv) {
+                    //tells a beginner why the move isn't allowed
+                    self.show_toast(&reason);
                 }
 
                 self.piece = (None, None);
+                self.drop_time = None;
 
             }
 
             //Replays the boards
             if self.replay_turn < 777 && self.status == BoardStatus::Checkmate {
 
-                if self.replay_turn < self.saved_replay[0].len() {
-                    self.board = self.saved_replay[0][self.replay_turn];
-                    println!("{}", self.replay_turn);        
+                if let Some(board) = self.replay_view.as_ref().and_then(|replay| replay.board_at(self.replay_turn)) {
+                    self.board = board;
                 }
             }
+
+        // the compare view covers the board when it is open
+        self.draw_compare(ctx).expect("Failed to draw compare view.");
+
+        self.draw_mobility(ctx).expect("Failed to draw mobility hint.");
+
+        self.draw_positional_hints(ctx).expect("Failed to draw positional hints.");
+
+        //the touched piece is marked while it waits to be moved
+        if let Some(touched) = self.touched {
+            let mut mark = Overlay::default();
+            mark.highlight(touched, graphics::Color::new(1.0, 0.8, 0.0, 0.35));
+            mark.draw(ctx, self.view_flipped(), self.font).expect("Failed to draw touched piece.");
+        }
+
+        // draw the overlay last so it ends up above the pieces
+        self.overlay.draw(ctx, self.view_flipped(), self.font).expect("Failed to draw overlay.");
+
+        self.draw_promotion_picker(ctx).expect("Failed to draw promotion picker.");
+
+        self.draw_presentation(ctx).expect("Failed to draw presentation.");
+
+        self.draw_lesson(ctx).expect("Failed to draw lesson.");
+
+        self.draw_analysis(ctx).expect("Failed to draw analysis keys.");
+
+        self.celebration.draw(ctx).expect("Failed to draw effects.");
+
+        self.draw_square_name(ctx).expect("Failed to draw square name.");
+
+        self.draw_toast(ctx).expect("Failed to draw toast.");
+
+        self.draw_confirm(ctx).expect("Failed to draw dialog.");
+
+        self.draw_position_problems(ctx).expect("Failed to draw dialog.");
+
+        self.draw_setup(ctx).expect("Failed to draw setup.");
+
+        self.draw_puzzle_draft(ctx).expect("Failed to draw dialog.");
+
+        self.draw_import_progress(ctx).expect("Failed to draw import progress.");
+
+        if self.show_stats {
+            self.draw_stats(ctx).expect("Failed to draw stats.");
+        }
+
+        if self.show_keys {
+            self.draw_key_sheet(ctx).expect("Failed to draw cheat sheet.");
+        }
     
         // render updated graphics
         graphics::present(ctx).expect("Failed to update graphics.");
@@ -649,14 +4125,29 @@ impl event::EventHandler<GameError> for AppState {
         _x: f32,
         _y: f32,
         ) {
+        //the scale is saved once the slider is let go
+        if button == event::MouseButton::Left && self.dragging_scale {
+            self.dragging_scale = false;
+            self.save_settings();
+        }
         if button == event::MouseButton::Left {
             /* check click position and update board accordingly */
             input::mouse::set_cursor_grabbed(ctx, false).ok();
+            if self.piece != (None, None) {
+                self.drop_time = Some(Instant::now());
+            }
            
            
         }
     }
 
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if self.dragging_scale && input::mouse::button_pressed(ctx, event::MouseButton::Left) {
+            let pos = window_to_screen(ctx, x, y);
+            self.drag_ui_scale(ctx, pos.x);
+        }
+    }
+
     fn mouse_button_down_event (
             &mut self,
             ctx: &mut Context,
@@ -664,70 +4155,415 @@ impl event::EventHandler<GameError> for AppState {
             x: f32,
             y: f32,
         )  { 
-        if button == event::MouseButton::Left  {
+        //the board is locked while a dialog waits for an answer, during the first-run setup, in kiosk mode and while a lesson is open
+        if self.confirm.is_some() || self.position_problems.is_some() || self.puzzle_draft.is_some() || self.setup.is_some() || self.kiosk.is_some() || self.lesson_view.is_some() {
+            return;
+        }
 
-            //Finds the rank and file position in f32
-            if ( 20.0 < x && x < GRID_CELL_SIZE.0 as f32 * 8.0 + 20.0) && ( 20.0 < y && y < GRID_CELL_SIZE.0 as f32 * 8.0 + 20.0) {
-                self.pos_x = (((x-20.0)/GRID_CELL_SIZE.0 as f32)).floor();
-                self.pos_y = (((y-20.0)/GRID_CELL_SIZE.0 as f32)).floor();
+        //works in unscaled screen coordinates, like the drawing
+        let pos = window_to_screen(ctx, x, y);
+        let (x, y) = (pos.x, pos.y);
 
-                input::mouse::set_cursor_grabbed(ctx, true).ok(); 
-            }
+        if button != event::MouseButton::Left {
+            return;
+        }
+        let region = self.hit_regions(ctx).at(x, y);
 
-            //Starts a new game
-            if self.status == BoardStatus::Checkmate && (x >= 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 && x <= 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 340.0) && (y >= 100.0 && y <= 160.0) {
-                self.board = Board::default();
-                self.status = BoardStatus::Ongoing;
-                self.game = Game::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").expect("Valid FEN");
-                self.side_to_move = Color::White;
-                self.piece = (None, None);
-                self.replay_boards.clear();
-                self.replay_boards.push(Board::default());
-                self.replay_turn = 999;
+        //a click on the promotion picker promotes, a click anywhere else takes the pawn back
+        if let Some((from, to)) = self.promotion_choice.take() {
+            if let Some(HitRegion::PromotionPiece(i)) = region {
+                self.play_move(chess::ChessMove::new(from, to, Some(PROMOTION_PIECES[i])));
             }
+            return;
+        }
 
-            //Updates replay_turn to 0 if you press Replay button
-            if self.status == BoardStatus::Checkmate && (x >= 40.0 + (GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32) && x <= 40.0 + GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32 + 340.0) && (y >= 160.0 && y <= 220.0) {
+        let top_color = if self.view_flipped() { Color::White } else { Color::Black };
+        match region {
+            Some(HitRegion::Board) => {
+                //Finds the rank and file position in f32
+                //the far edge of the board belongs to the last square
+                self.pos_x = ((x - 20.0) / GRID_CELL_SIZE.0 as f32).floor().min(7.0);
+                self.pos_y = ((y - 20.0) / GRID_CELL_SIZE.1 as f32).floor().min(7.0);
+
+                //the other side's pieces stay where they are
+                let sq = screen_square(self.pos_y as usize, self.pos_x as usize, self.view_flipped());
+                match self.input_lock(sq) {
+                    Some(reason) => self.show_toast(&reason),
+                    None => {
+                        self.touch(sq);
+                        self.grab_point = [x, y];
+                        input::mouse::set_cursor_grabbed(ctx, true).ok();
+                    }
+                }
+            }
+            //Clicking a name plate before the game starts picks the next profile for that side
+            Some(HitRegion::TopNamePlate) => self.next_profile(ctx, top_color),
+            Some(HitRegion::BottomNamePlate) => self.next_profile(ctx, !top_color),
+            //Clicking the UI scale slider sets the scale at that point, dragging moves it along
+            Some(HitRegion::ScaleSlider) => {
+                self.dragging_scale = true;
+                self.drag_ui_scale(ctx, x);
+            }
+            //Starts the next game of the match with colors swapped
+            Some(HitRegion::RematchButton) => self.rematch(),
+            Some(HitRegion::StartButton) => self.new_game(),
+            Some(HitRegion::AnalysisButton) => self.start_analysis(),
+            //Updates replay_turn to 0 if you press Replay button, the list picks another game than the last one
+            Some(HitRegion::ReplaysButton) => {
                 self.replay_turn = 0;
+                self.replay_view = self.saved_replay.first().cloned();
             }
-            
-            
-
-       
-        } 
+            Some(HitRegion::ReplayEntry(i)) => {
+                self.replay_turn = 0;
+                self.replay_view = self.filtered_replays().get(i).map(|&index| self.saved_replay[index].clone());
+                self.replay_search = None;
+            }
+            Some(HitRegion::PromotionPiece(_)) | None => {}
+        }
     }
 
     fn key_down_event(
             &mut self,
-            _ctx: &mut Context,
+            ctx: &mut Context,
             keycode: event::KeyCode,
-            _keymods: event::KeyMods,
+            keymods: event::KeyMods,
             _repeat: bool,
         ) {
-        if keycode == event::KeyCode::D && self.replay_turn >= self.replay_boards.len() { self.replay_turn += 1; }
-        if keycode == event::KeyCode::A && self.replay_turn >= 1 { self.replay_turn -= 1; }
+        //kiosk mode only listens to Escape, which quits
+        if self.kiosk.is_some() {
+            if keycode == event::KeyCode::Escape {
+                event::quit(ctx);
+            }
+            return;
+        }
+
+        //the puzzle dialog takes the keyboard until it is saved or cancelled, the title is typed in text_input_event
+        if let Some(draft) = &mut self.puzzle_draft {
+            match keycode {
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => self.save_puzzle_draft(),
+                event::KeyCode::Escape => self.puzzle_draft = None,
+                event::KeyCode::Back => { draft.field().pop(); }
+                event::KeyCode::Tab => draft.editing_themes = !draft.editing_themes,
+                event::KeyCode::Up => draft.difficulty = draft.difficulty.harder(),
+                event::KeyCode::Down => draft.difficulty = draft.difficulty.easier(),
+                _ => {}
+            }
+            return;
+        }
+
+        //the replay search takes the keyboard until a game is opened or the search is closed, it is typed in text_input_event
+        if let Some(search) = &mut self.replay_search {
+            match keycode {
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => {
+                    if let Some(&index) = self.filtered_replays().first() {
+                        self.replay_turn = 0;
+                        self.replay_view = Some(self.saved_replay[index].clone());
+                        self.replay_search = None;
+                    }
+                }
+                event::KeyCode::Escape => self.replay_search = None,
+                event::KeyCode::Back => { search.pop(); }
+                _ => {}
+            }
+            return;
+        }
+
+        //the note of a lesson step takes the keyboard until it is recorded or cancelled, it is typed in text_input_event
+        if let Some(presentation) = &mut self.presentation {
+            if let Some(note) = &mut presentation.note {
+                match keycode {
+                    event::KeyCode::Return | event::KeyCode::NumpadEnter => self.record_step(),
+                    event::KeyCode::Escape => presentation.note = None,
+                    event::KeyCode::Back => { note.pop(); }
+                    _ => {}
+                }
+                return;
+            }
+        }
+
+        //the first-run setup takes the keyboard until it is finished or skipped
+        if let Some(step) = self.setup {
+            match keycode {
+                event::KeyCode::Left | event::KeyCode::Right | event::KeyCode::Space => match step {
+                    SetupStep::Texture => self.theme.next_texture(ctx),
+                    SetupStep::SquareNames => self.settings.square_names = !self.settings.square_names,
+                    SetupStep::TouchMove => self.settings.touch_move = !self.settings.touch_move,
+                },
+                event::KeyCode::Return | event::KeyCode::NumpadEnter => self.setup = step.next(),
+                event::KeyCode::Escape => self.setup = None,
+                _ => {}
+            }
+            if self.setup.is_none() {
+                self.settings.texture = self.theme.texture_name();
+                self.save_settings();
+                println!("Setup done, the choices can be changed later in {}.", self.settings_path.display());
+            }
+            return;
+        }
+
+        //the refused position stays until it is closed, or a legal FEN replaces it
+        if self.position_problems.is_some() {
+            if keycode == event::KeyCode::Escape {
+                self.position_problems = None;
+            }
+            return;
+        }
+
+        //Y and N answer an open dialog, nothing else happens until then
+        if let Some(confirm) = self.confirm {
+            match keycode {
+                event::KeyCode::Y => {
+                    self.confirm = None;
+                    match confirm {
+                        Confirm::Resign => {
+                            self.game.resign(self.side_to_move);
+                            self.end_game(Some(!self.side_to_move), format!("{:?} resigned, {:?} won!", self.side_to_move, !self.side_to_move));
+                        }
+                        Confirm::OfferDraw => {
+                            self.game.offer_draw(self.side_to_move);
+                            self.confirm = Some(Confirm::AcceptDraw);
+                        }
+                        Confirm::AcceptDraw => {
+                            self.game.accept_draw();
+                            self.end_game(None, "Draw by agreement.".to_string());
+                        }
+                    }
+                }
+                event::KeyCode::N | event::KeyCode::Escape => {
+                    if confirm == Confirm::AcceptDraw {
+                        println!("{:?} declined the draw offer.", !self.side_to_move);
+                    }
+                    self.confirm = None;
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        //the bindings are looked up first, an action can change which contexts are active
+        let command = keymods.contains(COMMAND_KEY);
+        let actions = KEY_BINDINGS.iter()
+            .filter(|binding| binding.key == keycode && binding.command == command && self.key_context_active(binding.context))
+            .map(|binding| binding.action)
+            .collect::<Vec<Action>>();
+        for action in actions {
+            self.run_action(ctx, action);
+        }
+    }
+
+    /// Keeps the view in proportion in fullscreen, kiosk mode shows everything and presentation mode the board.
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        let area = if self.kiosk.is_some() {
+            graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1)
+        } else if self.presentation.is_some() {
+            let board = GRID_SIZE as f32 * GRID_CELL_SIZE.1 as f32;
+            graphics::Rect::new(10.0, 10.0, board + 20.0, board + 20.0)
+        } else {
+            return;
+        };
+        let scale = (width / area.w).min(height / area.h);
+        let (view_width, view_height) = (width / scale, height / scale);
+        let view = graphics::Rect::new(
+            area.x + (area.w - view_width) / 2.0,
+            area.y + (area.h - view_height) / 2.0,
+            view_width,
+            view_height,
+        );
+        if let Err(e) = graphics::set_screen_coordinates(ctx, view) {
+            println!("Failed to fit the view: {}", e);
+        }
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        self.focused = gained;
+    }
+
+    /// Types the title or themes of a recorded puzzle, or the note of a lesson step.
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+        if let Some(draft) = &mut self.puzzle_draft {
+            if !draft.typing {
+                draft.typing = true;
+                if character.eq_ignore_ascii_case(&'p') {
+                    return;
+                }
+            }
+            if !character.is_control() && character != ';' && draft.field().chars().count() < 30 {
+                draft.field().push(character);
+            }
+        }
+
+        if let Some(search) = &mut self.replay_search {
+            if !character.is_control() && search.chars().count() < 30 {
+                search.push(character);
+            }
+        }
+
+        if let Some(presentation) = &mut self.presentation {
+            if let Some(note) = &mut presentation.note {
+                if !presentation.typing {
+                    presentation.typing = true;
+                    if character.eq_ignore_ascii_case(&'n') {
+                        return;
+                    }
+                }
+                if !character.is_control() && note.chars().count() < 60 {
+                    note.push(character);
+                }
+            }
+        }
+    }
+
+}
+
+
+/// Converts a position in the window to screen coordinates, which the UI scale and fullscreen views change.
+fn window_to_screen(ctx: &Context, x: f32, y: f32) -> ggez::mint::Point2<f32> {
+    view_point(graphics::screen_coordinates(ctx), graphics::drawable_size(ctx), x, y)
+}
+
+/// Converts a position in a window of a size to the screen coordinates of the view shown in it.
+fn view_point(view: graphics::Rect, (width, height): (f32, f32), x: f32, y: f32) -> ggez::mint::Point2<f32> {
+    ggez::mint::Point2 { x: view.x + x * view.w / width, y: view.y + y * view.h / height }
+}
+
+/// The settings file of a profile, with the characters a file name can't have replaced.
+fn user_file_name(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    format!("{}.txt", name)
+}
+
+/// The data directory next to the executable in portable mode, None when the user config directory is used.
+/// Portable mode is turned on with --portable or a file named "portable" next to the executable,
+/// so the game can run from a USB stick. Nothing is written to the user config directory then, vsync included.
+fn portable_dir() -> Option<path::PathBuf> {
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    if std::env::args().any(|arg| arg == "--portable") || exe_dir.join("portable").exists() {
+        Some(exe_dir.join("data"))
+    } else {
+        None
     }
+}
 
+/// The resources directory in the working directory, or else next to the executable.
+/// Windows starts the app in the opened file's directory when a .pgn or .fen file is opened with it.
+/// In a macOS app bundle the resources are in Contents/Resources, next to the Contents/MacOS executable directory.
+fn resources_dir() -> path::PathBuf {
+    let local = path::PathBuf::from("./resources");
+    if local.exists() {
+        return local;
+    }
+    let exe_dir = match std::env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.to_path_buf())) {
+        Some(dir) => dir,
+        None => return local,
+    };
+    let bundled = exe_dir.join("../Resources/resources");
+    if cfg!(target_os = "macos") && bundled.exists() {
+        return bundled;
+    }
+    exe_dir.join("resources")
 }
 
+/// The window config, it is also written to the ggez config file when vsync is changed outside portable mode.
+fn window_conf(vsync: bool) -> conf::Conf {
+    conf::Conf {
+        window_setup: conf::WindowSetup::default()
+            .title("Schack") // Set window title "Schack"
+            .icon(if resources_dir().join(PIECES_DIR).join("icon.png").exists() { "/icon.png" } else { "" }) // Set application icon, without the resources directory the window has the default icon
+            .vsync(vsync),
+        window_mode: conf::WindowMode::default()
+            .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1) // Set window dimensions
+            .resizable(false), // Fixate window size
+        ..conf::Conf::default()
+    }
+}
 
 pub fn main() -> GameResult {
-    let resource_dir = path::PathBuf::from("./resources/pieces-png");
+    let resource_dir = resources_dir().join(PIECES_DIR);
+    let texture_dir = resources_dir().join("JohnPablok Cburnett Chess set/PNGs/No shadow/128h");
+    let font_dir = resources_dir().join("fonts");
 
+    //portable mode keeps vsync in the settings beside the executable
+    let vsync = portable_dir().map_or(true, |dir| settings::Settings::load(&dir.join("settings.txt")).vsync);
     let context_builder = ContextBuilder::new("schack", "olle")
         .add_resource_path(resource_dir) // Import image files to GGEZ
-        .window_setup(
-            conf::WindowSetup::default()
-                .title("Schack") // Set window title "Schack"
-                .icon("/icon.png"), // Set application icon
-        )
-        .window_mode(
-            conf::WindowMode::default()
-                .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1) // Set window dimensions
-                .resizable(false), // Fixate window size
-        );
+        .add_resource_path(texture_dir) // Board square textures
+        .add_resource_path(font_dir) // Bundled font
+        .default_conf(window_conf(vsync)); // A saved config file with another vsync setting replaces this
     let (mut contex, mut _event_loop) = context_builder.build().expect("Failed to build context.");
 
-    let state = AppState::new(&mut contex).expect("Failed to create state.");
+    let mut state = AppState::new(&mut contex).expect("Failed to create state.");
+    let args = std::env::args().collect::<Vec<String>>();
+    //--kiosk games.pgn plays the games in a loop in fullscreen, for a screen at a club or event
+    if let Some(path) = args.iter().position(|arg| arg == "--kiosk").and_then(|i| args.get(i + 1)) {
+        state.start_kiosk(&mut contex, path);
+    }
+    //--stream turns on stream mode without changing the settings
+    if args.iter().any(|arg| arg == "--stream") {
+        state.stream_mode = true;
+    }
+    //--seed 1234 replays the puzzles and drills of an earlier session
+    let seed = args.iter().position(|arg| arg == "--seed").and_then(|i| args.get(i + 1)).and_then(|seed| seed.parse().ok());
+    state.reseed(seed.unwrap_or_else(Rng::time_seed));
+    //an unfinished game in the journal continues where it was left
+    state.recover_journal();
+    //commands can be written to stdin, e.g. "move e2e4"
+    remote::read_stdin(state.inbox.clone());
+    //--serve 127.0.0.1:8080 starts the API server
+    if let Some(i) = args.iter().position(|arg| arg == "--serve") {
+        state.serve(args.get(i + 1).map_or("127.0.0.1:8080", |address| address.as_str()));
+    }
+    //a .pgn or .fen path opens the file, this is how the installer's file associations start the app
+    let file = args.iter().enumerate().skip(1)
+        .find(|(i, arg)| !arg.starts_with("--") && !matches!(args.get(i - 1).map(|arg| arg.as_str()), Some("--serve") | Some("--seed") | Some("--kiosk")));
+    if let Some((_, path)) = file {
+        state.inbox.lock().expect("Inbox lock poisoned").push(remote::Command::Open(path.clone()));
+    }
     event::run(contex, _event_loop, state) // Run window event loop
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The window position of a screen position, the inverse of view_point.
+    fn window_point(view: graphics::Rect, (width, height): (f32, f32), [x, y]: [f32; 2]) -> (f32, f32) {
+        ((x - view.x) * width / view.w, (y - view.y) * height / view.h)
+    }
+
+    #[test]
+    fn squares_round_trip_through_the_window() {
+        let screen = graphics::Rect::new(0.0, 0.0, SCREEN_SIZE.0, SCREEN_SIZE.1);
+        //the plain window, a UI scale of 150%, and a fullscreen view with bars at the sides
+        let windows = [
+            (screen, SCREEN_SIZE),
+            (screen, (SCREEN_SIZE.0 * 1.5, SCREEN_SIZE.1 * 1.5)),
+            (graphics::Rect::new(-300.0, 0.0, SCREEN_SIZE.0 + 600.0, SCREEN_SIZE.1), (2560.0, 1440.0)),
+        ];
+        for (view, size) in windows.iter() {
+            for flipped in [false, true] {
+                for sq in chess::ALL_SQUARES.iter() {
+                    let (x, y) = window_point(*view, *size, square_center(*sq, flipped));
+                    let pos = view_point(*view, *size, x, y);
+                    assert_eq!(square_at(pos.x, pos.y, flipped), Some(*sq));
+                    //the other orientation turns the board around
+                    assert_eq!(square_at(pos.x, pos.y, !flipped).map(|other| other.to_index()), Some(63 - sq.to_index()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn user_file_names_are_safe() {
+        assert_eq!(user_file_name("Player 1"), "Player_1.txt");
+        assert_eq!(user_file_name("../Åsa"), "___Åsa.txt");
+    }
+
+    #[test]
+    fn positions_off_the_board_have_no_square() {
+        let board_size = GRID_SIZE as f32 * GRID_CELL_SIZE.0 as f32;
+        for (x, y) in [(10.0, 100.0), (100.0, 10.0), (20.0 + board_size + 1.0, 100.0), (100.0, 20.0 + board_size + 1.0)] {
+            assert_eq!(square_at(x, y, false), None);
+            assert_eq!(square_at(x, y, true), None);
+        }
+    }
+}
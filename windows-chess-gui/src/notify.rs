@@ -0,0 +1,44 @@
+/**
+ * Desktop notifications, sent through the tools every platform already has:
+ * a PowerShell balloon tip on Windows, osascript on macOS and notify-send elsewhere.
+ */
+
+use std::process::{Command, Stdio};
+
+/// Quotes text for a single-quoted PowerShell or AppleScript string.
+fn quoted(text: &str, quote: char) -> String {
+    let escaped = match quote {
+        '\'' => text.replace('\'', "''"),
+        _ => text.replace('\\', "\\\\").replace('"', "\\\""),
+    };
+    format!("{}{}{}", quote, escaped, quote)
+}
+
+/// Shows a notification without waiting for it, failures are only logged.
+pub fn send(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "windows") {
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(5000, {}, {}, 'Info'); Start-Sleep 6; $n.Dispose()",
+            quoted(title, '\''),
+            quoted(body, '\''),
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args(["-e", &format!("display notification {} with title {}", quoted(body, '"'), quoted(title, '"'))]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args([title, body]);
+        command
+    };
+
+    let spawned = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+    if let Err(e) = spawned {
+        println!("Failed to show a notification: {}", e);
+    }
+}
@@ -0,0 +1,359 @@
+/**
+ * Writes games as PGN, with the moves in standard algebraic notation.
+ * Games can also be written as a Markdown report for forums and homework.
+ * The main line of a PGN game can be read back, comments and variations are skipped.
+ */
+
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::eval;
+
+/// Letter of a piece in SAN, pawns have none.
+fn piece_letter(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "",
+        Piece::Knight => "N",
+        Piece::Bishop => "B",
+        Piece::Rook => "R",
+        Piece::Queen => "Q",
+        Piece::King => "K",
+    }
+}
+
+/// Writes a legal move of a position in SAN, e.g. "Nbd7", "exd5", "e8=Q+" or "O-O".
+pub fn san(board: &Board, mv: ChessMove) -> String {
+    let from = mv.get_source();
+    let to = mv.get_dest();
+    let piece = board.piece_on(from).expect("Move from an empty square");
+
+    let mut text = String::new();
+    let file_change = from.get_file().to_index() as i32 - to.get_file().to_index() as i32;
+
+    if piece == Piece::King && file_change.abs() == 2 {
+        text.push_str(if file_change < 0 { "O-O" } else { "O-O-O" });
+    } else {
+        let capture = board.piece_on(to).is_some() || (piece == Piece::Pawn && file_change != 0);
+        let square = to.to_string();
+
+        if piece == Piece::Pawn {
+            if capture {
+                text.push_str(&from.to_string()[..1]);
+            }
+        } else {
+            text.push_str(piece_letter(piece));
+
+            //adds the file, rank or both when another piece of the same kind can go to the same square
+            let others = MoveGen::new_legal(board)
+                .filter(|other| other.get_dest() == to && other.get_source() != from && board.piece_on(other.get_source()) == Some(piece))
+                .map(|other| other.get_source())
+                .collect::<Vec<_>>();
+            if !others.is_empty() {
+                let same_file = others.iter().any(|sq| sq.get_file() == from.get_file());
+                let same_rank = others.iter().any(|sq| sq.get_rank() == from.get_rank());
+                let from_name = from.to_string();
+                if !same_file {
+                    text.push_str(&from_name[..1]);
+                } else if !same_rank {
+                    text.push_str(&from_name[1..]);
+                } else {
+                    text.push_str(&from_name);
+                }
+            }
+        }
+
+        if capture {
+            text.push('x');
+        }
+        text.push_str(&square);
+
+        if let Some(promotion) = mv.get_promotion() {
+            text.push('=');
+            text.push_str(piece_letter(promotion));
+        }
+    }
+
+    let after = board.make_move_new(mv);
+    if after.status() == BoardStatus::Checkmate {
+        text.push('#');
+    } else if after.checkers().popcnt() > 0 {
+        text.push('+');
+    }
+
+    text
+}
+
+/// Finds the legal move written in SAN, ignoring check marks and annotations like "!?".
+pub fn parse_san(board: &Board, text: &str) -> Option<ChessMove> {
+    let bare = |san: &str| san.trim_end_matches(|c| matches!(c, '+' | '#' | '!' | '?')).to_string();
+    //castling is sometimes written with zeros
+    let text = if text.starts_with("0-0") { bare(&text.replace('0', "O")) } else { bare(text) };
+    MoveGen::new_legal(board).find(|mv| bare(&san(board, *mv)) == text)
+}
+
+/// Reads the first game of a PGN text as its start position and main line.
+pub fn read(text: &str) -> Result<(Board, Vec<ChessMove>), String> {
+    let mut start = Board::default();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(tag) = line.strip_prefix('[') {
+            if !movetext.trim().is_empty() {
+                break;
+            }
+            if let Some(fen) = tag.strip_prefix("FEN \"").and_then(|fen| fen.split('"').next()) {
+                start = Board::from_str(fen).map_err(|e| format!("Bad FEN tag: {}", e))?;
+            }
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    //comments and variations are skipped, variations can be nested
+    let mut main_line = String::new();
+    let mut depth = 0;
+    let mut in_comment = false;
+    let mut in_line_comment = false;
+    for c in movetext.chars() {
+        match c {
+            '\n' if in_line_comment => in_line_comment = false,
+            _ if in_line_comment => {}
+            '}' if in_comment => in_comment = false,
+            _ if in_comment => {}
+            '{' => in_comment = true,
+            ';' => in_line_comment = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err("A variation is closed that was never opened".to_string()),
+            ')' => depth -= 1,
+            _ if depth > 0 => {}
+            c => main_line.push(c),
+        }
+    }
+    //a cut off game would otherwise load as if it ended there
+    if depth > 0 {
+        return Err("A variation is opened but never closed".to_string());
+    }
+    if in_comment {
+        return Err("A comment is opened but never closed".to_string());
+    }
+
+    let mut board = start;
+    let mut moves = vec![];
+    for token in main_line.split_whitespace() {
+        if token.starts_with('$') || matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            continue;
+        }
+        //move numbers can be written together with the move, e.g. "1.e4"
+        let token = if token.starts_with("0-0") { token } else { token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.') };
+        if token.is_empty() {
+            continue;
+        }
+        let mv = parse_san(&board, token).ok_or_else(|| format!("{} is not a legal move after {} moves", token, moves.len()))?;
+        moves.push(mv);
+        board = board.make_move_new(mv);
+    }
+
+    Ok((start, moves))
+}
+
+/// The value of a tag in the tag section of a PGN game, e.g. tag(text, "White").
+pub fn tag(text: &str, name: &str) -> Option<String> {
+    let prefix = format!("[{} \"", name);
+    text.lines()
+        .map(|line| line.trim())
+        .take_while(|line| line.is_empty() || line.starts_with('['))
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+        .and_then(|value| value.split('"').next())
+        .map(|value| value.to_string())
+}
+
+/// Today's date in the PGN format, e.g. "2022.10.16".
+pub fn date_today() -> String {
+    let days = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(time) => (time.as_secs() / 86400) as i64,
+        Err(_) => return "????.??.??".to_string(),
+    };
+
+    //converts days since 1970-01-01 to a calendar date
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Writes a game from a start position with its tag pairs, moves and result.
+/// Games that don't start from the starting position get SetUp and FEN tags.
+pub fn write(tags: &[(&str, String)], start: &Board, moves: &[ChessMove], result: &str) -> String {
+    let mut text = String::new();
+    for (name, value) in tags.iter() {
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    text.push_str(&format!("[Result \"{}\"]\n", result));
+    if *start != Board::default() {
+        text.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", start));
+    }
+    text.push('\n');
+
+    //a game with Black to move starts at "1..."
+    let offset = if start.side_to_move() == Color::Black { 1 } else { 0 };
+
+    //PGN lines should be kept under 80 characters
+    let mut line = String::new();
+    let mut board = *start;
+    for (i, mv) in moves.iter().enumerate() {
+        let ply = i + offset;
+        let mut token = String::new();
+        if ply % 2 == 0 {
+            token.push_str(&format!("{}. ", ply / 2 + 1));
+        } else if i == 0 {
+            token.push_str(&format!("{}... ", ply / 2 + 1));
+        }
+        token.push_str(&san(&board, *mv));
+        board = board.make_move_new(*mv);
+
+        if !line.is_empty() && line.len() + token.len() + 1 > 79 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    if !line.is_empty() && line.len() + result.len() + 1 > 79 {
+        text.push_str(&line);
+        text.push('\n');
+        line.clear();
+    }
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    line.push_str(result);
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+/// Writes a game as a Markdown report: a title, the tags as a list, a numbered move table and the result.
+/// With `evals` every row also gets the static evaluation after the moves, in pawns from White's view.
+pub fn markdown(tags: &[(&str, String)], start: &Board, moves: &[ChessMove], result: &str, evals: bool) -> String {
+    let tag = |name: &str| tags.iter().find(|(tag, _)| *tag == name).map_or("?".to_string(), |(_, value)| value.clone());
+    let mut text = format!("# {} vs {}\n\n", tag("White"), tag("Black"));
+    for (name, value) in tags.iter() {
+        text.push_str(&format!("- **{}:** {}\n", name, value));
+    }
+    text.push_str(&format!("- **Result:** {}\n", result));
+    if *start != Board::default() {
+        text.push_str(&format!("- **FEN:** `{}`\n", start));
+    }
+    text.push('\n');
+
+    text.push_str(if evals { "| # | White | Black | Eval |\n|--:|---|---|--:|\n" } else { "| # | White | Black |\n|--:|---|---|\n" });
+
+    //a game with Black to move starts with an empty White cell
+    let mut board = *start;
+    let mut row = vec![];
+    let mut number = 1;
+    if start.side_to_move() == Color::Black {
+        row.push("...".to_string());
+    }
+    for mv in moves.iter() {
+        row.push(san(&board, *mv));
+        board = board.make_move_new(*mv);
+        if row.len() == 2 {
+            text.push_str(&table_row(number, &row, evals.then(|| eval::evaluate(&board))));
+            row.clear();
+            number += 1;
+        }
+    }
+    if !row.is_empty() {
+        row.push(String::new());
+        text.push_str(&table_row(number, &row, evals.then(|| eval::evaluate(&board))));
+    }
+
+    text.push_str(&format!("\n**{}**\n", result));
+    text
+}
+
+/// One row of the Markdown move table.
+fn table_row(number: usize, moves: &[String], eval: Option<i32>) -> String {
+    match eval {
+        Some(eval) => format!("| {} | {} | {} | {:+.2} |\n", number, moves[0], moves[1], eval as f32 / 100.0),
+        None => format!("| {} | {} | {} |\n", number, moves[0], moves[1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::{Board, MoveGen};
+    use std::str::FromStr;
+
+    const POSITIONS: &[&str] = &[
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "4k3/8/8/8/8/2N3N1/8/R3K2R w KQ - 0 1",
+        "1n2k3/P7/8/8/8/8/6p1/4K2R w K - 0 1",
+        "1n2k3/P7/8/8/8/8/6p1/4K2R b - - 0 1",
+    ];
+
+    #[test]
+    fn san_round_trips_every_legal_move() {
+        for fen in POSITIONS.iter() {
+            let board = Board::from_str(fen).expect("Test FEN");
+            for mv in MoveGen::new_legal(&board) {
+                let text = san(&board, mv);
+                assert_eq!(parse_san(&board, &text), Some(mv), "{} in {}", text, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn written_game_reads_back() {
+        let mut board = Board::default();
+        let mut moves = vec![];
+        for _ in 0..30 {
+            let mv = match MoveGen::new_legal(&board).last() {
+                Some(mv) => mv,
+                None => break,
+            };
+            moves.push(mv);
+            board = board.make_move_new(mv);
+        }
+        let text = write(&[("White", "A".to_string()), ("Black", "B".to_string())], &Board::default(), &moves, "*");
+        let (start, read_moves) = read(&text).expect("Written game");
+        assert!(start == Board::default());
+        assert_eq!(read_moves, moves);
+    }
+
+    #[test]
+    fn comments_and_variations_are_skipped() {
+        let (_, moves) = read("1. e4 {best by test} e5 (1... c5 2. Nf3 (2. c3)) 2. Nf3 ; a comment\n Nc6 *").expect("Test game");
+        assert_eq!(moves.len(), 4);
+    }
+
+    #[test]
+    fn unbalanced_variation_is_an_error() {
+        assert!(read("1. e4 e5) 2. Nf3 *").is_err());
+        assert!(read("1. e4 e5 2. Nf3 (2. c3 Nc6 3. d4 *").is_err());
+    }
+
+    #[test]
+    fn unclosed_comment_is_an_error() {
+        assert!(read("1. e4 e5 2. Nf3 {a long note Nc6 3. Bb5 *").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+/**
+ * Player profiles, stored one per line in a profiles file in the user config directory.
+ * Line format: name;preferred color;rating;avatar;wins;losses;draws
+ * where preferred color is White, Black or Any and rating/avatar can be left empty.
+ */
+
+use chess::Color;
+use std::{fs, io, path::Path};
+
+/// A player that can be picked for either side before a game starts.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+
+    pub preferred_color: Option<Color>,
+
+    pub rating: Option<u32>,
+    // Image path inside the resources directory, e.g. "/avatar.png".
+    pub avatar: Option<String>,
+
+    pub wins: u32,
+
+    pub losses: u32,
+
+    pub draws: u32,
+}
+
+impl Profile {
+
+    pub fn new(name: &str, preferred_color: Option<Color>) -> Profile {
+        Profile {
+            name: name.to_string(),
+            preferred_color,
+            rating: None,
+            avatar: None,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+        }
+    }
+
+    /// Reads a profile from a line of the profiles file, None if the line is not a profile.
+    fn parse(line: &str) -> Option<Profile> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let fields: Vec<&str> = line.split(';').map(|field| field.trim()).collect();
+        if fields.is_empty() || fields[0].is_empty() {
+            return None;
+        }
+        let field = |i: usize| fields.get(i).copied().unwrap_or("");
+
+        Some(Profile {
+            name: fields[0].to_string(),
+            preferred_color: match field(1) {
+                "White" => Some(Color::White),
+                "Black" => Some(Color::Black),
+                _ => None,
+            },
+            rating: field(2).parse().ok(),
+            avatar: if field(3).is_empty() { None } else { Some(field(3).to_string()) },
+            wins: field(4).parse().unwrap_or(0),
+            losses: field(5).parse().unwrap_or(0),
+            draws: field(6).parse().unwrap_or(0),
+        })
+    }
+
+    /// Writes the profile as a line of the profiles file.
+    fn to_line(&self) -> String {
+        format!(
+            "{};{};{};{};{};{};{}",
+            self.name,
+            match self.preferred_color {
+                Some(color) => format!("{:?}", color),
+                None => "Any".to_string(),
+            },
+            self.rating.map(|rating| rating.to_string()).unwrap_or_default(),
+            self.avatar.clone().unwrap_or_default(),
+            self.wins,
+            self.losses,
+            self.draws,
+        )
+    }
+}
+
+/// Loads all profiles from a profiles file, an empty list if the file can't be read.
+pub fn load(path: &Path) -> Vec<Profile> {
+    match fs::read_to_string(path) {
+        Ok(text) => text.lines().filter_map(Profile::parse).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Saves all profiles to a profiles file, creating its directory if needed.
+pub fn save(path: &Path, profiles: &[Profile]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut text = String::from("# name;preferred color (White/Black/Any);rating;avatar;wins;losses;draws\n");
+    for profile in profiles {
+        text.push_str(&profile.to_line());
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
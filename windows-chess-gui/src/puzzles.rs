@@ -0,0 +1,311 @@
+/**
+ * Local puzzle database, stored one puzzle per line in a puzzles file in the user config directory.
+ * Line format: title;rating;fen;solution;themes
+ * where the solution is the moves in UCI notation separated by spaces, starting with the side to move,
+ * and the themes are tags like fork, pin, backRankMate or endgame separated by spaces.
+ * The file can be shared as it is, or puzzles can be copied line by line.
+ * Puzzles can also be imported from the lichess puzzle database CSV.
+ */
+
+use chess::Board;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::remote;
+
+/// Imported puzzles are written to the puzzles file in batches of this size.
+const IMPORT_BATCH: usize = 1000;
+
+/// How hard a puzzle is, stored as a rating so imported puzzles keep theirs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+
+    Medium,
+
+    Hard,
+}
+
+impl Difficulty {
+
+    pub fn rating(self) -> u32 {
+        match self {
+            Difficulty::Easy => 1200,
+            Difficulty::Medium => 1600,
+            Difficulty::Hard => 2000,
+        }
+    }
+
+    pub fn from_rating(rating: u32) -> Difficulty {
+        match rating {
+            0..=1399 => Difficulty::Easy,
+            1400..=1799 => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    pub fn harder(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        }
+    }
+
+    pub fn easier(self) -> Difficulty {
+        match self {
+            Difficulty::Hard => Difficulty::Medium,
+            _ => Difficulty::Easy,
+        }
+    }
+}
+
+/// A position with the line that solves it.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub title: String,
+
+    pub rating: u32,
+
+    pub fen: String,
+    // Moves in UCI notation, e.g. "e2e4", the solving side plays every other move.
+    pub solution: Vec<String>,
+    // Motifs of the puzzle, lichess theme names for imported puzzles.
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+
+    /// Reads a puzzle from a line of the puzzles file, None if the line is not a puzzle.
+    fn parse(line: &str) -> Option<Puzzle> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let fields: Vec<&str> = line.split(';').map(|field| field.trim()).collect();
+        if fields.len() < 4 || fields[2].is_empty() || fields[3].is_empty() {
+            return None;
+        }
+
+        Some(Puzzle {
+            title: fields[0].to_string(),
+            rating: fields[1].parse().unwrap_or(Difficulty::Medium.rating()),
+            fen: fields[2].to_string(),
+            solution: fields[3].split_whitespace().map(|mv| mv.to_string()).collect(),
+            themes: fields.get(4).map_or(vec![], |themes| themes.split_whitespace().map(|theme| theme.to_string()).collect()),
+        })
+    }
+
+    /// True when the puzzle is tagged with a theme, in any letter case.
+    pub fn has_theme(&self, theme: &str) -> bool {
+        self.themes.iter().any(|tag| tag.eq_ignore_ascii_case(theme))
+    }
+
+    /// Writes the puzzle as a line of the puzzles file.
+    fn to_line(&self) -> String {
+        format!(
+            "{};{};{};{};{}",
+            self.title.replace(';', ","),
+            self.rating,
+            self.fen,
+            self.solution.join(" "),
+            self.themes.join(" ").replace(';', ","),
+        )
+    }
+}
+
+/// Loads all puzzles from a puzzles file, an empty list if the file can't be read.
+pub fn load(path: &Path) -> Vec<Puzzle> {
+    match fs::read_to_string(path) {
+        Ok(text) => text.lines().filter_map(Puzzle::parse).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// All themes the puzzles are tagged with, sorted and without duplicates.
+pub fn themes(puzzles: &[Puzzle]) -> Vec<String> {
+    let mut themes = puzzles.iter().flat_map(|puzzle| puzzle.themes.iter().cloned()).collect::<Vec<String>>();
+    themes.sort();
+    themes.dedup();
+    themes
+}
+
+/// Adds puzzles to the end of a puzzles file, creating it and its directory if needed.
+pub fn append(path: &Path, puzzles: &[Puzzle]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let new_file = !path.exists();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if new_file {
+        writeln!(file, "# title;rating;fen;solution (UCI moves);themes")?;
+    }
+    for puzzle in puzzles {
+        writeln!(file, "{}", puzzle.to_line())?;
+    }
+    Ok(())
+}
+
+/// Which lichess puzzles to keep when importing.
+#[derive(Clone, Debug)]
+pub struct ImportFilter {
+    pub min_rating: u32,
+
+    pub max_rating: u32,
+    // Only puzzles tagged with this lichess theme, e.g. "fork".
+    pub theme: Option<String>,
+}
+
+/// How far an import has come, shared with the thread that runs it.
+#[derive(Clone, Debug, Default)]
+pub struct ImportProgress {
+    pub bytes_read: u64,
+
+    pub total_bytes: u64,
+    // Puzzles read from the CSV.
+    pub read: usize,
+    // Puzzles that passed the filter and were saved.
+    pub kept: usize,
+    // Puzzles that passed the filter but were already in the puzzles file.
+    pub duplicates: usize,
+
+    pub done: bool,
+
+    pub error: Option<String>,
+}
+
+pub type SharedProgress = Arc<Mutex<ImportProgress>>;
+
+/// Converts a line of the lichess CSV to a puzzle, None if it is the header, filtered out or broken.
+/// Columns: PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+fn from_lichess(line: &str, filter: &ImportFilter) -> Option<Puzzle> {
+    let fields: Vec<&str> = line.trim().split(',').collect();
+    if fields.len() < 8 {
+        return None;
+    }
+
+    let rating = fields[3].parse::<u32>().ok()?;
+    if rating < filter.min_rating || rating > filter.max_rating {
+        return None;
+    }
+    if let Some(theme) = &filter.theme {
+        if !fields[7].split_whitespace().any(|tag| tag.eq_ignore_ascii_case(theme)) {
+            return None;
+        }
+    }
+
+    //the FEN is before the opponent's last move, which is the first move of the line
+    let moves: Vec<&str> = fields[2].split_whitespace().collect();
+    let board = Board::from_str(fields[1]).ok()?;
+    let first = remote::parse_uci(moves.first()?)?;
+    if moves.len() < 2 || !board.legal(first) {
+        return None;
+    }
+
+    Some(Puzzle {
+        title: format!("Lichess {}", fields[0]),
+        rating,
+        fen: board.make_move_new(first).to_string(),
+        solution: moves[1..].iter().map(|mv| mv.to_string()).collect(),
+        themes: fields[7].split_whitespace().map(|theme| theme.to_string()).collect(),
+    })
+}
+
+/// Imports the lichess puzzle database CSV on its own thread, adding the puzzles that pass the filter
+/// to the puzzles file. The CSV is read line by line since it has millions of puzzles.
+/// Puzzles already in the file are skipped by their title, so the same CSV can be imported again.
+/// The dump is compressed with zstd and has to be decompressed first.
+pub fn import_lichess(csv_path: PathBuf, puzzles_path: PathBuf, filter: ImportFilter, progress: SharedProgress) {
+    thread::spawn(move || {
+        let result = (|| -> io::Result<()> {
+            let file = fs::File::open(&csv_path)?;
+            progress.lock().expect("Import progress lock poisoned").total_bytes = file.metadata()?.len();
+
+            //the title holds the lichess puzzle id
+            let mut known = load(&puzzles_path).into_iter().map(|puzzle| puzzle.title).collect::<HashSet<String>>();
+            let mut batch = vec![];
+            let mut bytes_read = 0;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                bytes_read += line.len() as u64 + 1;
+                let puzzle = from_lichess(&line, &filter);
+                let (kept, duplicate) = match puzzle {
+                    Some(puzzle) if known.insert(puzzle.title.clone()) => {
+                        batch.push(puzzle);
+                        (true, false)
+                    }
+                    Some(_) => (false, true),
+                    None => (false, false),
+                };
+                if batch.len() >= IMPORT_BATCH {
+                    append(&puzzles_path, &batch)?;
+                    batch.clear();
+                }
+
+                let mut progress = progress.lock().expect("Import progress lock poisoned");
+                progress.bytes_read = bytes_read;
+                progress.read += 1;
+                if kept {
+                    progress.kept += 1;
+                }
+                if duplicate {
+                    progress.duplicates += 1;
+                }
+            }
+            append(&puzzles_path, &batch)
+        })();
+
+        let mut progress = progress.lock().expect("Import progress lock poisoned");
+        progress.done = true;
+        if let Err(e) = result {
+            progress.error = Some(e.to_string());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LICHESS_LINE: &str = "00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,https://lichess.org/yyznGmXs/black#34,Italian_Game";
+
+    fn filter(min_rating: u32, max_rating: u32, theme: Option<&str>) -> ImportFilter {
+        ImportFilter { min_rating, max_rating, theme: theme.map(|theme| theme.to_string()) }
+    }
+
+    #[test]
+    fn lichess_line_starts_after_the_first_move() {
+        let puzzle = from_lichess(LICHESS_LINE, &filter(0, 3000, None)).expect("Test puzzle");
+        assert_eq!(puzzle.title, "Lichess 00sHx");
+        assert_eq!(puzzle.rating, 1760);
+        assert_eq!(puzzle.solution, vec!["a2e6", "d7d8", "f7f8"]);
+        assert!(puzzle.fen.contains(" w "));
+    }
+
+    #[test]
+    fn lichess_filter_skips_puzzles() {
+        assert!(from_lichess(LICHESS_LINE, &filter(1800, 3000, None)).is_none());
+        assert!(from_lichess(LICHESS_LINE, &filter(0, 3000, Some("fork"))).is_none());
+        assert!(from_lichess(LICHESS_LINE, &filter(0, 3000, Some("MATEIN2"))).is_some());
+        assert!(from_lichess("PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags", &filter(0, 3000, None)).is_none());
+    }
+
+    #[test]
+    fn puzzle_line_round_trips() {
+        let line = "Back rank;1600;6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1;a1a8;backRankMate mateIn1";
+        let puzzle = Puzzle::parse(line).expect("Test puzzle");
+        assert_eq!(puzzle.solution, vec!["a1a8"]);
+        assert!(puzzle.has_theme("backrankmate"));
+        assert_eq!(puzzle.to_line(), line);
+        assert!(Puzzle::parse("# a comment").is_none());
+        assert!(Puzzle::parse("No moves;1200;8/8/8/8/8/8/8/8 w - - 0 1;").is_none());
+    }
+}
@@ -0,0 +1,250 @@
+/**
+ * Remote control of the GUI.
+ * A small HTTP server shares the running game and takes moves, and commands can be
+ * written to stdin. Both are queued as commands and carried out by the GUI in update().
+ */
+
+use chess::{ChessMove, Piece, Square};
+use crate::puzzles::ImportFilter;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// How long a connection may take to send its request before it is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something another program asked the GUI to do.
+#[derive(Clone, Debug)]
+pub enum Command {
+    NewGame,
+    // A move in UCI notation, e.g. "e2e4" or "e7e8q".
+    Move(String),
+    // Starts a game from a position.
+    Fen(String),
+
+    Flip,
+    // Opens a .pgn or .fen file.
+    Open(String),
+    // Saves the game as PGN to a path.
+    SavePgn(String),
+    // Saves the game as a Markdown report to a path, with evaluations when the flag is set.
+    SaveMarkdown(String, bool),
+    // Saves the game as a printable PDF scoresheet to a path.
+    SaveScoresheet(String),
+    // Imports the lichess puzzle CSV at a path.
+    ImportPuzzles(String, ImportFilter),
+    // Imports every game of a PGN file at a path as replays.
+    ImportGames(String),
+}
+
+impl Command {
+
+    /// Reads a stdin command: newgame, move e2e4, fen <fen>, flip, open <path>, save pgn <path>,
+    /// save md <path>, save md+eval <path>, save pdf <path>, import puzzles <min>-<max> <theme or all> <csv path>
+    /// or import games <pgn path>.
+    pub fn parse(line: &str) -> Option<Command> {
+        let line = line.trim();
+        let (word, rest) = line.split_once(' ').map_or((line, ""), |(word, rest)| (word, rest.trim()));
+
+        match word {
+            "newgame" if rest.is_empty() => Some(Command::NewGame),
+            "move" if parse_uci(rest).is_some() => Some(Command::Move(rest.to_string())),
+            "fen" if !rest.is_empty() => Some(Command::Fen(rest.to_string())),
+            "flip" if rest.is_empty() => Some(Command::Flip),
+            "open" if !rest.is_empty() => Some(Command::Open(rest.to_string())),
+            "save" => match rest.split_once(' ') {
+                Some(("pgn", path)) => Some(Command::SavePgn(path.trim().to_string())),
+                Some(("md", path)) => Some(Command::SaveMarkdown(path.trim().to_string(), false)),
+                Some(("md+eval", path)) => Some(Command::SaveMarkdown(path.trim().to_string(), true)),
+                Some(("pdf", path)) => Some(Command::SaveScoresheet(path.trim().to_string())),
+                _ => None,
+            },
+            "import" if rest.starts_with("games ") => Some(Command::ImportGames(rest["games ".len()..].trim().to_string())),
+            "import" => {
+                let mut args = rest.strip_prefix("puzzles ")?.trim().splitn(3, ' ');
+                let (min, max) = args.next()?.split_once('-')?;
+                let theme = args.next()?;
+                let path = args.next()?.trim();
+                let filter = ImportFilter {
+                    min_rating: min.parse().ok()?,
+                    max_rating: max.parse().ok()?,
+                    theme: if theme == "all" { None } else { Some(theme.to_string()) },
+                };
+                if path.is_empty() { None } else { Some(Command::ImportPuzzles(path.to_string(), filter)) }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Commands waiting to be carried out, shared between the GUI and the threads that receive them.
+pub type Inbox = Arc<Mutex<Vec<Command>>>;
+
+/// The running game as the GUI last shared it.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub fen: String,
+    // Moves in SAN from the starting position.
+    pub moves: Vec<String>,
+
+    pub status: String,
+}
+
+pub type SharedState = Arc<Mutex<Snapshot>>;
+
+/// Reads a move in UCI notation, None if it is not one.
+pub fn parse_uci(text: &str) -> Option<ChessMove> {
+    let text = text.trim();
+    if text.len() < 4 || text.len() > 5 || !text.is_ascii() {
+        return None;
+    }
+
+    let from = Square::from_str(&text[0..2]).ok()?;
+    let to = Square::from_str(&text[2..4]).ok()?;
+    let promotion = match text.get(4..5) {
+        None => None,
+        Some("q") => Some(Piece::Queen),
+        Some("r") => Some(Piece::Rook),
+        Some("b") => Some(Piece::Bishop),
+        Some("n") => Some(Piece::Knight),
+        Some(_) => return None,
+    };
+
+    Some(ChessMove::new(from, to, promotion))
+}
+
+/// Reads commands from stdin on its own thread, one per line, until stdin closes.
+pub fn read_stdin(inbox: Inbox) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Command::parse(&line) {
+                Some(command) => inbox.lock().expect("Inbox lock poisoned").push(command),
+                None => println!("Unknown command: {}", line.trim()),
+            }
+        }
+    });
+}
+
+/// Writes a string as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl Snapshot {
+
+    /// The snapshot as a JSON object. There are no clocks yet, so "clocks" is always null.
+    fn to_json(&self) -> String {
+        let moves = self.moves.iter().map(|mv| json_string(mv)).collect::<Vec<_>>().join(",");
+        format!(
+            "{{\"fen\":{},\"moves\":[{}],\"status\":{},\"clocks\":null}}",
+            json_string(&self.fen),
+            moves,
+            json_string(&self.status),
+        )
+    }
+}
+
+/// Starts the HTTP server on its own thread.
+///
+/// `GET /state` answers with the snapshot as JSON and `POST /move` queues the UCI move in the body.
+/// Every connection gets its own thread, so a slow or idle client doesn't hold up the others.
+pub fn serve(address: &str, state: SharedState, inbox: Inbox) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("Serving the game on http://{}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (state, inbox) = (state.clone(), inbox.clone());
+                    thread::spawn(move || {
+                        if let Err(e) = handle(stream, &state, &inbox) {
+                            println!("API request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => println!("API connection failed: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answers one request, every connection is closed after its answer.
+/// A client that doesn't send the whole request in time gets no answer.
+fn handle(stream: TcpStream, state: &SharedState, inbox: &Inbox) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("").to_string();
+
+    //only the body length is needed from the headers
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().unwrap_or(0).min(1024);
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    let (status, content) = match (method.as_str(), target.as_str()) {
+        ("GET", "/state") => {
+            let snapshot = state.lock().expect("Shared state lock poisoned");
+            ("200 OK", snapshot.to_json())
+        }
+        ("POST", "/move") => match parse_uci(&body) {
+            Some(_) => {
+                inbox.lock().expect("Inbox lock poisoned").push(Command::Move(body.trim().to_string()));
+                ("202 Accepted", "{\"queued\":true}".to_string())
+            }
+            None => ("400 Bad Request", "{\"error\":\"expected a move like e2e4\"}".to_string()),
+        },
+        _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content.len(),
+        content,
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,211 @@
+/**
+ * Finished games kept for replays, stored compactly as the start position and the moves packed in 16 bits each.
+ * Every tenth position is kept as a checkpoint, so seeking to a ply plays at most a few moves.
+ * The players, date and result are kept with the opening so the replay list can be searched.
+ */
+
+use chess::{Board, ChessMove, Piece, Square, ALL_SQUARES};
+
+use crate::{eco, pgn};
+
+/// Plies between the positions kept as checkpoints.
+const CHECKPOINT_INTERVAL: usize = 10;
+
+/// Packs a move as 6 bits from square, 6 bits to square and 3 bits promotion, 0 for none.
+pub fn encode(mv: ChessMove) -> u16 {
+    let promotion = match mv.get_promotion() {
+        None => 0,
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(_) => 4,
+    };
+    mv.get_source().to_index() as u16 | (mv.get_dest().to_index() as u16) << 6 | promotion << 12
+}
+
+/// Unpacks a move packed by `encode`.
+pub fn decode(packed: u16) -> ChessMove {
+    let square = |index: u16| -> Square { ALL_SQUARES[(index & 63) as usize] };
+    let promotion = match packed >> 12 & 7 {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    };
+    ChessMove::new(square(packed), square(packed >> 6), promotion)
+}
+
+/// Who played a saved game, when and how it ended. A field is empty when it isn't known.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameInfo {
+    pub white: String,
+
+    pub black: String,
+
+    // PGN date, e.g. "2022.10.16".
+    pub date: String,
+
+    // PGN result, e.g. "1-0".
+    pub result: String,
+}
+
+impl GameInfo {
+
+    /// The info in the tag section of a PGN game.
+    pub fn from_pgn(text: &str) -> GameInfo {
+        let tag = |name: &str| pgn::tag(text, name).unwrap_or_default();
+        GameInfo { white: tag("White"), black: tag("Black"), date: tag("Date"), result: tag("Result") }
+    }
+}
+
+#[derive(Clone)]
+pub struct Replay {
+    // The start position and every CHECKPOINT_INTERVAL'th position after it.
+    checkpoints: Vec<Board>,
+
+    moves: Vec<u16>,
+
+    opening: Option<eco::Opening>,
+
+    info: GameInfo,
+}
+
+impl Replay {
+
+    pub fn new(start: Board, moves: &[ChessMove]) -> Replay {
+        let mut checkpoints = vec![start];
+        let mut board = start;
+        for (i, mv) in moves.iter().enumerate() {
+            board = board.make_move_new(*mv);
+            if (i + 1) % CHECKPOINT_INTERVAL == 0 {
+                checkpoints.push(board);
+            }
+        }
+
+        Replay {
+            checkpoints,
+            moves: moves.iter().map(|mv| encode(*mv)).collect(),
+            opening: eco::classify(&start, moves),
+            info: GameInfo::default(),
+        }
+    }
+
+    pub fn with_info(mut self, info: GameInfo) -> Replay {
+        self.info = info;
+        self
+    }
+
+    pub fn info(&self) -> &GameInfo {
+        &self.info
+    }
+
+    /// True when every word of a search is in the players, date, result or opening of the game, in any letter case.
+    /// Dates are written like 2022.10.16, so "2022.10" finds the games of a month.
+    pub fn matches(&self, search: &str) -> bool {
+        let opening = self.opening.map_or(String::new(), |opening| opening.label());
+        let text = format!("{} {} {} {} {}", self.info.white, self.info.black, self.info.date, self.info.result, opening).to_lowercase();
+        search.to_lowercase().split_whitespace().all(|word| text.contains(word))
+    }
+
+    pub fn moves(&self) -> Vec<ChessMove> {
+        self.moves.iter().map(|packed| decode(*packed)).collect()
+    }
+
+    /// The start position and the position after every move.
+    pub fn boards(&self) -> Vec<Board> {
+        let mut boards = vec![self.checkpoints[0]];
+        for packed in self.moves.iter() {
+            boards.push(boards[boards.len() - 1].make_move_new(decode(*packed)));
+        }
+        boards
+    }
+
+    /// The position after a number of moves, from the nearest checkpoint before it. None past the end.
+    pub fn board_at(&self, ply: usize) -> Option<Board> {
+        if ply > self.moves.len() {
+            return None;
+        }
+        let checkpoint = ply / CHECKPOINT_INTERVAL;
+        let moves = &self.moves[checkpoint * CHECKPOINT_INTERVAL..ply];
+        Some(moves.iter().fold(self.checkpoints[checkpoint], |board, packed| board.make_move_new(decode(*packed))))
+    }
+
+    /// The opening the game was classified as when it was saved.
+    pub fn opening(&self) -> Option<eco::Opening> {
+        self.opening
+    }
+
+    /// Number of positions in the replay, the start position included.
+    pub fn len(&self) -> usize {
+        self.moves.len() + 1
+    }
+
+    /// Bytes the replay takes, roughly.
+    pub fn memory(&self) -> usize {
+        std::mem::size_of::<Replay>()
+            + self.checkpoints.len() * std::mem::size_of::<Board>()
+            + self.moves.len() * std::mem::size_of::<u16>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::MoveGen;
+    use std::str::FromStr;
+
+    #[test]
+    fn encode_and_decode_round_trip() {
+        //the second position has promotions with and without a capture for both sides
+        for fen in &["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "1n2k3/P7/8/8/8/8/6p1/4K2R w K - 0 1", "1n2k3/P7/8/8/8/8/6p1/4K2R b - - 0 1"] {
+            let board = Board::from_str(fen).expect("Test FEN");
+            for mv in MoveGen::new_legal(&board) {
+                assert_eq!(decode(encode(mv)), mv);
+            }
+        }
+    }
+
+    #[test]
+    fn board_at_matches_boards() {
+        let mut board = Board::default();
+        let mut moves = vec![];
+        for _ in 0..(3 * CHECKPOINT_INTERVAL + 4) {
+            let mv = match MoveGen::new_legal(&board).next() {
+                Some(mv) => mv,
+                None => break,
+            };
+            moves.push(mv);
+            board = board.make_move_new(mv);
+        }
+        assert!(moves.len() > 2 * CHECKPOINT_INTERVAL, "the test game crosses the checkpoints");
+
+        let replay = Replay::new(Board::default(), &moves);
+        let boards = replay.boards();
+        assert_eq!(boards.len(), replay.len());
+        for (ply, expected) in boards.iter().enumerate() {
+            assert!(replay.board_at(ply) == Some(*expected), "ply {}", ply);
+        }
+        assert!(replay.board_at(replay.len()).is_none());
+        assert_eq!(replay.moves(), moves);
+    }
+
+    #[test]
+    fn search_finds_players_results_and_openings() {
+        let mut board = Board::default();
+        let mut moves = vec![];
+        for san in "e4 e5 Nf3 Nc6 Bc4 Bc5".split_whitespace() {
+            let mv = pgn::parse_san(&board, san).expect("Test move");
+            moves.push(mv);
+            board = board.make_move_new(mv);
+        }
+        let info = GameInfo::from_pgn("[White \"Anna Berg\"]\n[Black \"Olle\"]\n[Date \"2022.10.16\"]\n[Result \"1-0\"]\n\n1. e4 *\n");
+        let replay = Replay::new(Board::default(), &moves).with_info(info);
+        assert!(replay.matches(""));
+        assert!(replay.matches("berg 1-0"));
+        assert!(replay.matches("OLLE c50"));
+        assert!(replay.matches("2022.10 italian"));
+        assert!(!replay.matches("berg 0-1"));
+        assert!(!replay.matches("sicilian"));
+    }
+}
@@ -0,0 +1,215 @@
+/**
+ * Printable scoresheets of games as PDF.
+ * The PDF is written by hand with the standard Helvetica fonts, so no fonts have to be embedded.
+ * A sheet has the game tags, a two-column move table and a diagram of the final position.
+ */
+
+use chess::{Board, ChessMove, Color, Piece, ALL_SQUARES};
+
+use crate::pgn;
+
+/// A4 in PDF points.
+const PAGE_SIZE: (f32, f32) = (595.0, 842.0);
+
+const MARGIN: f32 = 50.0;
+
+const ROW_HEIGHT: f32 = 16.0;
+// Move rows in each of the two columns of a page.
+const ROWS_PER_COLUMN: usize = 25;
+
+const DIAGRAM_SQUARE: f32 = 24.0;
+
+/// Content of one page as PDF drawing operators.
+#[derive(Default)]
+struct Page {
+    content: String,
+}
+
+impl Page {
+
+    fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        self.content.push_str(&format!(
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n",
+            if bold { "F2" } else { "F1" },
+            size,
+            x,
+            y,
+            pdf_string(text),
+        ));
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32)) {
+        self.content.push_str(&format!("{:.1} {:.1} m {:.1} {:.1} l S\n", from.0, from.1, to.0, to.1));
+    }
+
+    /// A rectangle filled with a gray level, 0 is black and 1 is white.
+    fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, gray: f32) {
+        self.content.push_str(&format!("{:.2} g {:.1} {:.1} {:.1} {:.1} re f 0 g\n", gray, x, y, width, height));
+    }
+
+    fn frame(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.content.push_str(&format!("{:.1} {:.1} {:.1} {:.1} re S\n", x, y, width, height));
+    }
+}
+
+/// Escapes text for a PDF string, characters outside ASCII are written as '?'.
+fn pdf_string(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// Letter of a piece on the diagram, uppercase for White and lowercase for Black like in FEN.
+fn diagram_letter(color: Color, piece: Piece) -> String {
+    let letter = match piece {
+        Piece::Pawn => "p",
+        Piece::Knight => "n",
+        Piece::Bishop => "b",
+        Piece::Rook => "r",
+        Piece::Queen => "q",
+        Piece::King => "k",
+    };
+    if color == Color::White { letter.to_uppercase() } else { letter.to_string() }
+}
+
+/// Draws a position with White at the bottom, its bottom left corner at x, y.
+fn draw_diagram(page: &mut Page, board: &Board, x: f32, y: f32) {
+    for sq in ALL_SQUARES.iter() {
+        let file = sq.get_file().to_index() as f32;
+        let rank = sq.get_rank().to_index() as f32;
+        let (left, bottom) = (x + file * DIAGRAM_SQUARE, y + rank * DIAGRAM_SQUARE);
+        if (sq.get_file().to_index() + sq.get_rank().to_index()) % 2 == 0 {
+            page.fill(left, bottom, DIAGRAM_SQUARE, DIAGRAM_SQUARE, 0.75);
+        }
+        if let (Some(piece), Some(color)) = (board.piece_on(*sq), board.color_on(*sq)) {
+            page.text(left + 7.0, bottom + 7.0, 13.0, color == Color::Black, &diagram_letter(color, piece));
+        }
+    }
+    page.frame(x, y, 8.0 * DIAGRAM_SQUARE, 8.0 * DIAGRAM_SQUARE);
+
+    //file letters and rank numbers around the diagram
+    for i in 0..8 {
+        let offset = i as f32 * DIAGRAM_SQUARE;
+        page.text(x + offset + 9.0, y - 12.0, 8.0, false, &((b'a' + i) as char).to_string());
+        page.text(x - 10.0, y + offset + 9.0, 8.0, false, &(i + 1).to_string());
+    }
+}
+
+/// Writes a game as a PDF scoresheet, continuing the move table on more pages for long games.
+pub fn write(tags: &[(&str, String)], start: &Board, moves: &[ChessMove], result: &str) -> Vec<u8> {
+    //the moves as rows of a White and a Black move, a game with Black to move starts with "..."
+    let mut rows: Vec<(String, String)> = vec![];
+    let mut board = *start;
+    for mv in moves.iter() {
+        let san = pgn::san(&board, *mv);
+        match board.side_to_move() {
+            Color::White => rows.push((san, String::new())),
+            Color::Black => match rows.last_mut() {
+                Some(row) => row.1 = san,
+                None => rows.push(("...".to_string(), san)),
+            },
+        }
+        board = board.make_move_new(*mv);
+    }
+
+    let per_page = 2 * ROWS_PER_COLUMN;
+    let page_count = ((rows.len() + per_page - 1) / per_page).max(1);
+    let tag = |name: &str| tags.iter().find(|(tag, _)| *tag == name).map_or("?".to_string(), |(_, value)| value.clone());
+
+    let mut pages = vec![];
+    for page_index in 0..page_count {
+        let mut page = Page::default();
+        let mut y = PAGE_SIZE.1 - MARGIN;
+
+        page.text(MARGIN, y, 18.0, true, &format!("{} vs {}", tag("White"), tag("Black")));
+        y -= 24.0;
+        let details = tags.iter()
+            .filter(|(name, _)| *name != "White" && *name != "Black")
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .chain(std::iter::once(format!("Result: {}", result)))
+            .collect::<Vec<String>>()
+            .join("   ");
+        page.text(MARGIN, y, 10.0, false, &details);
+        if page_count > 1 {
+            page.text(PAGE_SIZE.0 - MARGIN - 60.0, PAGE_SIZE.1 - MARGIN, 10.0, false, &format!("Page {}/{}", page_index + 1, page_count));
+        }
+        y -= 30.0;
+
+        //two columns of numbered rows, the left column is filled first
+        let column_width = (PAGE_SIZE.0 - 2.0 * MARGIN) / 2.0;
+        for column in 0..2 {
+            let x = MARGIN + column as f32 * column_width;
+            page.text(x + 4.0, y, 10.0, true, "#");
+            page.text(x + 34.0, y, 10.0, true, "White");
+            page.text(x + 134.0, y, 10.0, true, "Black");
+            page.line((x, y - 4.0), (x + column_width - 10.0, y - 4.0));
+
+            for row in 0..ROWS_PER_COLUMN {
+                let index = page_index * per_page + column * ROWS_PER_COLUMN + row;
+                let row_y = y - (row + 1) as f32 * ROW_HEIGHT;
+                page.text(x + 4.0, row_y, 10.0, false, &(index + 1).to_string());
+                if let Some((white, black)) = rows.get(index) {
+                    page.text(x + 34.0, row_y, 10.0, false, white);
+                    page.text(x + 134.0, row_y, 10.0, false, black);
+                }
+                page.line((x, row_y - 4.0), (x + column_width - 10.0, row_y - 4.0));
+            }
+        }
+
+        //the final position goes under the table of the last page
+        if page_index + 1 == page_count {
+            let diagram_y = MARGIN + 20.0;
+            draw_diagram(&mut page, &board, MARGIN + 10.0, diagram_y);
+            let side = if board.side_to_move() == Color::White { "White" } else { "Black" };
+            let (notes_x, notes_y) = (MARGIN + 30.0 + 8.0 * DIAGRAM_SQUARE, diagram_y + 8.0 * DIAGRAM_SQUARE - 10.0);
+            page.text(notes_x, notes_y, 10.0, true, "Final position");
+            page.text(notes_x, notes_y - 16.0, 10.0, false, &format!("{} to move", side));
+            page.text(notes_x, notes_y - 32.0, 10.0, false, &format!("Result: {}", result));
+        }
+
+        pages.push(page);
+    }
+
+    document(&pages)
+}
+
+/// Puts the pages together as a PDF file, with the cross-reference table of object offsets.
+fn document(pages: &[Page]) -> Vec<u8> {
+    //objects 1 to 4 are the catalog, the page tree and the two fonts, then a page and its content for every page
+    let kids = (0..pages.len()).map(|i| format!("{} 0 R", 5 + 2 * i)).collect::<Vec<String>>().join(" ");
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_SIZE.0,
+            PAGE_SIZE.1,
+            6 + 2 * i,
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", page.content.len(), page.content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+
+    let xref = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref));
+
+    pdf.into_bytes()
+}
@@ -0,0 +1,118 @@
+/**
+ * User settings, stored as "key = value" lines in a settings file in the user config directory.
+ * Unknown keys and broken lines are skipped so an old or hand edited file still loads.
+ */
+
+use std::{fs, io, path::Path};
+
+/// Settings that are kept between runs.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // Path to a TTF font used instead of the bundled one.
+    pub font: Option<String>,
+    // File that is rewritten with the running game after every move, for streaming overlays.
+    pub live_pgn: Option<String>,
+    // Board-only layout on a chroma key background, for capture in streaming software.
+    pub stream_mode: bool,
+    // Waits for the screen refresh before showing a frame, only read when the window is created.
+    pub vsync: bool,
+    // Most frames per second, None for no limit.
+    pub fps_cap: Option<u32>,
+    // Shows the name of the square under the cursor, for learning the coordinates.
+    pub square_names: bool,
+    // Strict touch-move: a piece that is picked up and can move has to be moved.
+    pub touch_move: bool,
+    // Name of the board texture, None for plain colored tiles.
+    pub texture: Option<String>,
+    // Slip protection: pixels a piece has to be dragged before it moves, 0 for off.
+    pub min_drag: u32,
+    // How big the whole window is drawn, from 0.75 to 2.0.
+    pub ui_scale: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            font: None,
+            live_pgn: None,
+            stream_mode: false,
+            vsync: true,
+            fps_cap: None,
+            square_names: false,
+            touch_move: false,
+            texture: None,
+            min_drag: 0,
+            ui_scale: 1.0,
+        }
+    }
+}
+
+impl Settings {
+
+    /// Loads the settings from a settings file, the defaults if the file can't be read.
+    pub fn load(path: &Path) -> Settings {
+        let mut settings = Settings::default();
+
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return settings,
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key {
+                "font" if !value.is_empty() => settings.font = Some(value.to_string()),
+                "live_pgn" if !value.is_empty() => settings.live_pgn = Some(value.to_string()),
+                "stream_mode" => settings.stream_mode = value == "true",
+                "vsync" => settings.vsync = value != "false",
+                "fps_cap" => settings.fps_cap = value.parse::<u32>().ok().filter(|cap| *cap > 0),
+                "square_names" => settings.square_names = value == "true",
+                "touch_move" => settings.touch_move = value == "true",
+                "texture" if !value.is_empty() => settings.texture = Some(value.to_string()),
+                "min_drag" => settings.min_drag = value.parse().unwrap_or(0),
+                "ui_scale" => settings.ui_scale = value.parse().unwrap_or(1.0),
+                _ => {}
+            }
+        }
+
+        settings
+    }
+
+    /// Saves the settings to a settings file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut text = String::new();
+        text.push_str("# Path to a TTF font to use instead of the bundled one.\n");
+        text.push_str(&format!("font = {}\n", self.font.clone().unwrap_or_default()));
+        text.push_str("# Path to a PGN file that is rewritten after every move, e.g. live.pgn. Empty turns it off.\n");
+        text.push_str(&format!("live_pgn = {}\n", self.live_pgn.clone().unwrap_or_default()));
+        text.push_str("# true draws only the board and the last move on a green background, same as --stream.\n");
+        text.push_str(&format!("stream_mode = {}\n", self.stream_mode));
+        text.push_str("# Vsync takes effect the next time the game starts.\n");
+        text.push_str(&format!("vsync = {}\n", self.vsync));
+        text.push_str("# Most frames per second, e.g. 30, 60 or 120. 0 is unlimited.\n");
+        text.push_str(&format!("fps_cap = {}\n", self.fps_cap.unwrap_or(0)));
+        text.push_str("# true shows the name of the square under the cursor, and where a dragged piece came from.\n");
+        text.push_str(&format!("square_names = {}\n", self.square_names));
+        text.push_str("# true turns on the touch-move rule, a piece that is picked up has to be moved if it can.\n");
+        text.push_str(&format!("touch_move = {}\n", self.touch_move));
+        text.push_str("# Board texture, Wood or Stone. Empty is plain colored tiles.\n");
+        text.push_str(&format!("texture = {}\n", self.texture.clone().unwrap_or_default()));
+        text.push_str("# Slip protection, pixels a piece has to be dragged before it moves, e.g. 40. 0 is off.\n");
+        text.push_str(&format!("min_drag = {}\n", self.min_drag));
+        text.push_str("# Size of the whole window, from 0.75 to 2, e.g. 1.5 for 150%.\n");
+        text.push_str(&format!("ui_scale = {}\n", self.ui_scale));
+        fs::write(path, text)
+    }
+}
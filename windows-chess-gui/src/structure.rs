@@ -0,0 +1,93 @@
+/**
+ * Positional hints for teaching, found with bitboard rules of thumb rather than search.
+ * Open files have no pawns, half-open files only pawns of the other side. A weak square on ranks 4 to 6 can no
+ * longer be guarded by a pawn of the other side, and an outpost is a weak square one's own pawn guards,
+ * where a knight can't be chased away by a pawn.
+ */
+
+use chess::{BitBoard, Board, Color, Piece, Square, ALL_FILES, ALL_SQUARES, EMPTY};
+
+/// The hints for one side, the files are given as the squares on them.
+pub struct Hints {
+    pub open_files: BitBoard,
+
+    pub half_open_files: BitBoard,
+    // Weak squares of the other side that aren't outposts.
+    pub weak_squares: Vec<Square>,
+
+    pub outposts: Vec<Square>,
+}
+
+/// Rank counted from the side's own back rank, 0 to 7.
+fn relative_rank(sq: Square, color: Color) -> usize {
+    match color {
+        Color::White => sq.get_rank().to_index(),
+        Color::Black => 7 - sq.get_rank().to_index(),
+    }
+}
+
+/// True when no pawn of the other side can ever guard the square, pawns only move forward.
+fn is_weak(board: &Board, sq: Square, color: Color) -> bool {
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+    let mut guards = chess::get_adjacent_files(sq.get_file()) & their_pawns;
+    !guards.any(|pawn| relative_rank(pawn, color) > relative_rank(sq, color))
+}
+
+/// The open and half-open files, weak squares and outposts for a side.
+pub fn hints(board: &Board, color: Color) -> Hints {
+    let pawns = *board.pieces(Piece::Pawn);
+    let our_pawns = pawns & *board.color_combined(color);
+
+    let mut open_files = EMPTY;
+    let mut half_open_files = EMPTY;
+    for file in ALL_FILES.iter() {
+        let squares = chess::get_file(*file);
+        if squares & pawns == EMPTY {
+            open_files |= squares;
+        } else if squares & our_pawns == EMPTY {
+            half_open_files |= squares;
+        }
+    }
+
+    let mut weak_squares = vec![];
+    let mut outposts = vec![];
+    for sq in ALL_SQUARES.iter().filter(|sq| (3..=5).contains(&relative_rank(**sq, color))) {
+        if !is_weak(board, *sq, color) {
+            continue;
+        }
+        //a pawn of the side guards the square when it stands where a pawn of the other side on the square would capture
+        if chess::get_pawn_attacks(*sq, !color, our_pawns) != EMPTY {
+            outposts.push(*sq);
+        } else {
+            weak_squares.push(*sq);
+        }
+    }
+
+    Hints { open_files, half_open_files, weak_squares, outposts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::File;
+    use std::str::FromStr;
+
+    #[test]
+    fn starting_position_has_no_hints() {
+        let hints = hints(&Board::default(), Color::White);
+        assert_eq!(hints.open_files, EMPTY);
+        assert_eq!(hints.half_open_files, EMPTY);
+        assert!(hints.weak_squares.is_empty());
+        assert!(hints.outposts.is_empty());
+    }
+
+    #[test]
+    fn finds_files_and_outposts() {
+        let board = Board::from_str("4k3/pp4pp/8/8/4P3/8/8/4K3 w - - 0 1").expect("Test FEN");
+        let hints = hints(&board, Color::White);
+        assert_eq!(hints.open_files, chess::get_file(File::C) | chess::get_file(File::D) | chess::get_file(File::F));
+        assert_eq!(hints.half_open_files & chess::get_file(File::A), chess::get_file(File::A));
+        assert!(hints.outposts.contains(&Square::D5) && hints.outposts.contains(&Square::F5));
+        assert!(hints.weak_squares.contains(&Square::E5));
+    }
+}
@@ -0,0 +1,99 @@
+/**
+ * Guess-the-move training on a saved game.
+ * The user guesses every move of one side, the other side's moves are played automatically.
+ * Without an engine, wrong guesses are scored with the static evaluation from eval.rs.
+ */
+
+use chess::{Board, ChessMove, Color, MoveGen};
+
+use crate::eval;
+
+/// How one guess went.
+#[derive(Clone, Copy, Debug)]
+pub struct Guess {
+    pub matched: bool,
+    // The move that was played in the game.
+    pub actual: ChessMove,
+    // Centipawns the guess was worse than the game move, 0 when it was as good or better.
+    pub loss: i32,
+}
+
+#[derive(Clone)]
+pub struct GuessTraining {
+    boards: Vec<Board>,
+    // Index of the shown position in `boards`.
+    ply: usize,
+
+    side: Color,
+
+    guesses: Vec<Guess>,
+}
+
+/// Finds the legal move that turns one position into the next.
+fn played_move(before: &Board, after: &Board) -> Option<ChessMove> {
+    MoveGen::new_legal(before).find(|mv| before.make_move_new(*mv) == *after)
+}
+
+impl GuessTraining {
+
+    /// Starts training on a game saved as the positions after every move, guessing the moves of `side`.
+    pub fn new(boards: Vec<Board>, side: Color) -> GuessTraining {
+        let mut training = GuessTraining {
+            boards,
+            ply: 0,
+            side,
+            guesses: vec![],
+        };
+        training.skip_to_guess();
+        training
+    }
+
+    /// Plays the other side's moves until it is the guessing side's turn or the game is over.
+    fn skip_to_guess(&mut self) {
+        while !self.finished() && self.position().side_to_move() != self.side {
+            self.ply += 1;
+        }
+    }
+
+    /// The position where the next move should be guessed.
+    pub fn position(&self) -> Board {
+        self.boards[self.ply]
+    }
+
+    /// True when there are no moves left to guess.
+    pub fn finished(&self) -> bool {
+        self.ply + 1 >= self.boards.len()
+    }
+
+    /// Scores a guess and moves on to the next one, None if the game has no move here.
+    pub fn guess(&mut self, mv: ChessMove) -> Option<Guess> {
+        if self.finished() {
+            return None;
+        }
+        let before = self.position();
+        let after = self.boards[self.ply + 1];
+        let actual = played_move(&before, &after)?;
+
+        //compares the positions after both moves from the guessing side's view
+        let sign = if self.side == Color::White { 1 } else { -1 };
+        let loss = (sign * (eval::evaluate(&after) - eval::evaluate(&before.make_move_new(mv)))).max(0);
+        let guess = Guess {
+            matched: mv == actual,
+            actual,
+            loss: if mv == actual { 0 } else { loss },
+        };
+
+        self.guesses.push(guess);
+        self.ply += 1;
+        self.skip_to_guess();
+        Some(guess)
+    }
+
+    /// Short summary of all guesses, e.g. "Guessed 7/20, avg loss 35 cp".
+    pub fn summary(&self) -> String {
+        let matched = self.guesses.iter().filter(|guess| guess.matched).count();
+        let total_loss = self.guesses.iter().map(|guess| guess.loss).sum::<i32>();
+        let average = if self.guesses.is_empty() { 0 } else { total_loss / self.guesses.len() as i32 };
+        format!("Guessed {}/{}, avg loss {} cp", matched, self.guesses.len(), average)
+    }
+}
@@ -0,0 +1,207 @@
+/**
+ * Explains why a FEN is not a legal position, instead of only failing to load it.
+ */
+
+use chess::{BitBoard, Color, File, Piece, Rank, Square, ALL_COLORS};
+use std::str::FromStr;
+
+/// The pieces of a FEN placement, read without any legality checks.
+struct Placement {
+    pieces: Vec<(Square, Color, Piece)>,
+}
+
+impl Placement {
+
+    fn parse(placement: &str, problems: &mut Vec<String>) -> Option<Placement> {
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            problems.push(format!("The board has {} ranks instead of 8.", ranks.len()));
+            return None;
+        }
+
+        let mut pieces = vec![];
+        for (i, rank) in ranks.iter().enumerate() {
+            let mut file = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    file += empty as usize;
+                    continue;
+                }
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => Piece::Pawn,
+                    'n' => Piece::Knight,
+                    'b' => Piece::Bishop,
+                    'r' => Piece::Rook,
+                    'q' => Piece::Queen,
+                    'k' => Piece::King,
+                    _ => {
+                        problems.push(format!("'{}' is not a piece.", c));
+                        return None;
+                    }
+                };
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                if file < 8 {
+                    pieces.push((Square::make_square(Rank::from_index(7 - i), File::from_index(file)), color, piece));
+                }
+                file += 1;
+            }
+            if file != 8 {
+                problems.push(format!("Rank {} has {} squares instead of 8.", 8 - i, file));
+                return None;
+            }
+        }
+
+        Some(Placement { pieces })
+    }
+
+    fn bitboard(&self, color: Option<Color>, piece: Option<Piece>) -> BitBoard {
+        self.pieces.iter()
+            .filter(|(_, c, p)| color.map_or(true, |color| color == *c) && piece.map_or(true, |piece| piece == *p))
+            .fold(BitBoard::new(0), |bb, (sq, _, _)| bb | BitBoard::from_square(*sq))
+    }
+
+    fn count(&self, color: Color, piece: Piece) -> u32 {
+        self.bitboard(Some(color), Some(piece)).popcnt()
+    }
+
+    /// The pieces of `by` that attack `sq`.
+    fn attackers(&self, sq: Square, by: Color) -> BitBoard {
+        let blockers = self.bitboard(None, None);
+        let queens = self.bitboard(Some(by), Some(Piece::Queen));
+        (chess::get_rook_moves(sq, blockers) & (self.bitboard(Some(by), Some(Piece::Rook)) | queens))
+            | (chess::get_bishop_moves(sq, blockers) & (self.bitboard(Some(by), Some(Piece::Bishop)) | queens))
+            | (chess::get_knight_moves(sq) & self.bitboard(Some(by), Some(Piece::Knight)))
+            | (chess::get_king_moves(sq) & self.bitboard(Some(by), Some(Piece::King)))
+            | chess::get_pawn_attacks(sq, !by, self.bitboard(Some(by), Some(Piece::Pawn)))
+    }
+
+    fn piece_on(&self, sq: Square) -> Option<(Color, Piece)> {
+        self.pieces.iter().find(|(s, _, _)| *s == sq).map(|(_, color, piece)| (*color, *piece))
+    }
+}
+
+/// Everything that makes a FEN illegal, empty when the position is fine.
+pub fn problems(fen: &str) -> Vec<String> {
+    let mut problems = vec![];
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 {
+        problems.push("A FEN needs at least the board, side to move, castling and en passant fields.".to_string());
+        if fields.is_empty() {
+            return problems;
+        }
+    }
+
+    let placement = match Placement::parse(fields[0], &mut problems) {
+        Some(placement) => placement,
+        None => return problems,
+    };
+
+    let side = match fields.get(1).copied() {
+        Some("w") => Some(Color::White),
+        Some("b") => Some(Color::Black),
+        Some(other) => {
+            problems.push(format!("The side to move is '{}', it should be w or b.", other));
+            None
+        }
+        None => None,
+    };
+
+    for color in ALL_COLORS.iter() {
+        let kings = placement.count(*color, Piece::King);
+        if kings != 1 {
+            problems.push(format!("{:?} has {} kings, there has to be exactly one.", color, kings));
+        }
+        let pawns = placement.count(*color, Piece::Pawn);
+        if pawns > 8 {
+            problems.push(format!("{:?} has {} pawns, at most 8 are possible.", color, pawns));
+        }
+        let pieces = placement.bitboard(Some(*color), None).popcnt();
+        if pieces > 16 {
+            problems.push(format!("{:?} has {} pieces, at most 16 are possible.", color, pieces));
+        }
+
+        //every piece above the starting count has to come from a promoted pawn
+        let extra = [(Piece::Queen, 1), (Piece::Rook, 2), (Piece::Bishop, 2), (Piece::Knight, 2)]
+            .iter()
+            .map(|(piece, start)| placement.count(*color, *piece).saturating_sub(*start))
+            .sum::<u32>();
+        if pawns <= 8 && extra > 8 - pawns {
+            problems.push(format!("{:?} has {} promoted pieces but only {} pawns are missing.", color, extra, 8 - pawns));
+        }
+    }
+
+    let back_ranks = BitBoard::new(0xff00_0000_0000_00ff);
+    let back_rank_pawns = placement.bitboard(None, Some(Piece::Pawn)) & back_ranks;
+    for sq in back_rank_pawns {
+        problems.push(format!("There is a pawn on {}, pawns can't stand on the first or last rank.", sq));
+    }
+
+    if let Some(side) = side {
+        let king = placement.bitboard(Some(!side), Some(Piece::King));
+        if king.popcnt() == 1 && placement.attackers(king.to_square(), side).popcnt() > 0 {
+            problems.push(format!("{:?} is in check but it is {:?}'s turn.", !side, side));
+        }
+        let own_king = placement.bitboard(Some(side), Some(Piece::King));
+        if own_king.popcnt() == 1 {
+            let checkers = placement.attackers(own_king.to_square(), !side).popcnt();
+            if checkers > 2 {
+                problems.push(format!("{:?} is checked by {} pieces, at most 2 are possible.", side, checkers));
+            }
+        }
+    }
+
+    //castling needs the king and rook on their starting squares
+    if let Some(castling) = fields.get(2).copied().filter(|castling| *castling != "-") {
+        for c in castling.chars() {
+            let (color, king, rook) = match c {
+                'K' => (Color::White, Square::E1, Square::H1),
+                'Q' => (Color::White, Square::E1, Square::A1),
+                'k' => (Color::Black, Square::E8, Square::H8),
+                'q' => (Color::Black, Square::E8, Square::A8),
+                _ => {
+                    problems.push(format!("'{}' is not a castling right.", c));
+                    continue;
+                }
+            };
+            if placement.piece_on(king) != Some((color, Piece::King)) || placement.piece_on(rook) != Some((color, Piece::Rook)) {
+                problems.push(format!("Castling right {} needs the king on {} and the rook on {}.", c, king, rook));
+            }
+        }
+    }
+
+    //an en passant square is behind a pawn that just moved two squares
+    if let (Some(ep), Some(side)) = (fields.get(3).copied().filter(|ep| *ep != "-"), side) {
+        let rank = if side == Color::White { "6" } else { "3" };
+        let pawn = Square::from_str(ep).ok().and_then(|sq| if side == Color::White { sq.down() } else { sq.up() });
+        if !ep.ends_with(rank) || pawn.and_then(|sq| placement.piece_on(sq)) != Some((!side, Piece::Pawn)) {
+            problems.push(format!("{} can't be the en passant square, no {:?} pawn just moved past it.", ep, !side));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_position_has_no_problems() {
+        assert!(problems("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_empty());
+    }
+
+    #[test]
+    fn explains_each_problem() {
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1", "White has 0 kings, there has to be exactly one."),
+            ("rnbqkbnP/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w KQq - 0 1", "There is a pawn on h8, pawns can't stand on the first or last rank."),
+            ("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", "Black is in check but it is White's turn."),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", "Castling right K needs the king on e1 and the rook on h1."),
+            ("4k3/8/8/8/8/8/8/4K3 w - e6 0 1", "e6 can't be the en passant square, no Black pawn just moved past it."),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1", "The board has 7 ranks instead of 8."),
+        ];
+        for (fen, problem) in cases.iter() {
+            assert_eq!(problems(fen), vec![problem.to_string()], "{}", fen);
+        }
+    }
+}
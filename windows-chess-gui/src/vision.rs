@@ -0,0 +1,137 @@
+/**
+ * Vision training: find every check and capture in a position before the time runs out.
+ * The answers are the legal moves from MoveGen that give check or capture something.
+ */
+
+use chess::{Board, ChessMove, MoveGen, Piece};
+
+/// Seconds to find the moves of one position.
+pub const TIME_LIMIT: f32 = 30.0;
+
+/// What a move made in the drill turned out to be.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attempt {
+    Found,
+
+    AlreadyFound,
+    // Legal, but neither a check nor a capture, or not legal at all.
+    Wrong,
+}
+
+#[derive(Clone)]
+pub struct VisionDrill {
+    board: Board,
+
+    targets: Vec<ChessMove>,
+
+    found: Vec<ChessMove>,
+
+    mistakes: u32,
+
+    time_left: f32,
+}
+
+/// True when a legal move gives check or captures a piece, en passant included.
+pub fn is_check_or_capture(board: &Board, mv: ChessMove) -> bool {
+    let capture = board.piece_on(mv.get_dest()).is_some()
+        || (board.piece_on(mv.get_source()) == Some(Piece::Pawn) && mv.get_source().get_file() != mv.get_dest().get_file());
+    capture || board.make_move_new(mv).checkers().popcnt() > 0
+}
+
+/// Every check and capture in a position, a promotion counts once as the queen promotion.
+pub fn checks_and_captures(board: &Board) -> Vec<ChessMove> {
+    MoveGen::new_legal(board)
+        .filter(|mv| matches!(mv.get_promotion(), None | Some(Piece::Queen)))
+        .filter(|mv| is_check_or_capture(board, *mv))
+        .collect()
+}
+
+impl VisionDrill {
+
+    pub fn new(board: Board) -> VisionDrill {
+        VisionDrill {
+            board,
+            targets: checks_and_captures(&board),
+            found: vec![],
+            mistakes: 0,
+            time_left: TIME_LIMIT,
+        }
+    }
+
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    pub fn found(&self) -> &[ChessMove] {
+        &self.found
+    }
+
+    /// The checks and captures that were not found.
+    pub fn missed(&self) -> Vec<ChessMove> {
+        self.targets.iter().filter(|mv| !self.found.contains(mv)).copied().collect()
+    }
+
+    /// Checks a move against the answers, nothing counts after the drill is finished.
+    pub fn attempt(&mut self, mv: ChessMove) -> Option<Attempt> {
+        if self.finished() {
+            return None;
+        }
+        let attempt = if self.found.contains(&mv) {
+            Attempt::AlreadyFound
+        } else if self.targets.contains(&mv) {
+            self.found.push(mv);
+            Attempt::Found
+        } else {
+            self.mistakes += 1;
+            Attempt::Wrong
+        };
+        Some(attempt)
+    }
+
+    /// Counts down the time limit.
+    pub fn tick(&mut self, seconds: f32) {
+        self.time_left = (self.time_left - seconds).max(0.0);
+    }
+
+    pub fn time_left(&self) -> f32 {
+        self.time_left
+    }
+
+    /// True when every move is found or the time is up.
+    pub fn finished(&self) -> bool {
+        self.time_left <= 0.0 || self.found.len() == self.targets.len()
+    }
+
+    /// Short progress or result, e.g. "Found 3/5, 1 wrong, 12 s left".
+    pub fn summary(&self) -> String {
+        let found = format!("Found {}/{}, {} wrong", self.found.len(), self.targets.len(), self.mistakes);
+        if self.finished() {
+            //accuracy counts wrong moves against the found ones
+            let tries = self.found.len() as u32 + self.mistakes;
+            let accuracy = if tries == 0 { 0 } else { 100 * self.found.len() as u32 / tries };
+            format!("{}, {}% accurate", found, accuracy)
+        } else {
+            format!("{}, {:.0} s left", found, self.time_left.ceil())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::Square;
+    use std::str::FromStr;
+
+    #[test]
+    fn starting_position_has_no_checks_or_captures() {
+        assert!(checks_and_captures(&Board::default()).is_empty());
+    }
+
+    #[test]
+    fn promotions_count_once() {
+        let board = Board::from_str("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").expect("Test FEN");
+        let found = checks_and_captures(&board);
+        assert!(found.contains(&ChessMove::new(Square::A7, Square::B8, Some(Piece::Queen))));
+        assert!(!found.contains(&ChessMove::new(Square::A7, Square::B8, Some(Piece::Knight))));
+    }
+}